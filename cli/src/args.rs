@@ -1,6 +1,9 @@
+use crate::build::{InstallLocation, InstallMode, InstallScope, PluginFormat, Vst3Sdk};
+use crate::cli::{Error, MessageFormat, Result};
+use crate::config::Preset;
 use bpaf::{Parser, construct};
 use owo_colors::OwoColorize;
-use std::str::FromStr;
+use std::{ffi::OsString, path::PathBuf, str::FromStr};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ArgsVst3 {
@@ -19,15 +22,11 @@ pub struct ArgsAppleSign {
 
 impl FromStr for ArgsVst3 {
     type Err = String;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_ascii_lowercase().as_str() {
-            "gpl" => Ok(ArgsVst3::Gpl),
-            "proprietary" => Ok(ArgsVst3::Proprietary),
-            _ => Err(format!(
-                "use either {} or {} as VST3 SDK",
-                "GPL".bold().bright_cyan(),
-                "PROPRIETARY".bold().bright_green()
-            )),
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.parse::<Vst3Sdk>() {
+            Ok(Vst3Sdk::Proprietary) => Ok(ArgsVst3::Proprietary),
+            Ok(_) => Ok(ArgsVst3::Gpl),
+            Err(e) => Err(format!("{}, {}", e.message(), e.notes().join(", "))),
         }
     }
 }
@@ -40,14 +39,36 @@ pub struct ArgsBuild {
     pub target: Vec<String>,
 
     pub features: Vec<String>,
-    pub all_features: bool,
-    pub no_default_features: bool,
+    pub all_features: Option<bool>,
+    pub no_default_features: Option<bool>,
+
+    pub jobs: Option<usize>,
+    pub install_targets: Option<bool>,
+}
+
+#[derive(Debug, Default)]
+pub struct ArgsInstallLocation {
+    pub dir: Option<PathBuf>,
+    pub clap_dir: Option<PathBuf>,
+    pub vst3_dir: Option<PathBuf>,
+    pub auv2_dir: Option<PathBuf>,
+    pub system_wide: Option<bool>,
+    pub subfolder: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct Args {
-    pub install: bool,
+    pub preset: Option<String>,
+    pub output_dir: Option<PathBuf>,
+
+    pub install: Option<InstallMode>,
+    pub install_location: ArgsInstallLocation,
+    pub validate: Option<bool>,
+    pub validator: Vec<String>,
+    pub validator_strictness: Option<u32>,
     pub verbose: bool,
+    pub message_format: MessageFormat,
+    pub timings: bool,
 
     pub codesign: Option<ArgsAppleSign>,
 
@@ -57,6 +78,164 @@ pub struct Args {
     pub clap: bool,
 }
 
+#[derive(Debug)]
+pub struct ArgsInspect {
+    pub bundle: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct ArgsUninstall {
+    pub packages: Vec<String>,
+    pub all: bool,
+    pub install_location: ArgsInstallLocation,
+}
+
+#[derive(Debug)]
+pub struct ArgsDoctor {
+    pub target: Vec<String>,
+    pub vst3: ArgsVst3,
+    pub auv2: bool,
+    pub sign: bool,
+}
+
+#[derive(Debug)]
+pub struct ArgsZigCc {
+    pub zig_triple: String,
+    pub args: Vec<OsString>,
+}
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum ArgsCommand {
+    Bundle(Args),
+    Watch(Args),
+    Inspect(ArgsInspect),
+    Uninstall(ArgsUninstall),
+    Doctor(ArgsDoctor),
+    ValidateClap(ArgsInspect),
+    ZigCc(ArgsZigCc),
+}
+
+impl ArgsInstallLocation {
+    pub fn with_preset(mut self, preset: &Preset) -> Self {
+        self.dir = self.dir.or(preset.install_dir.clone());
+        self.clap_dir = self.clap_dir.or(preset.install_dir_clap.clone());
+        self.vst3_dir = self.vst3_dir.or(preset.install_dir_vst3.clone());
+        self.auv2_dir = self.auv2_dir.or(preset.install_dir_auv2.clone());
+        self.system_wide = self.system_wide.or(preset.system_wide);
+        self.subfolder = self.subfolder.or(preset.install_subfolder.clone());
+        self
+    }
+
+    pub fn location(&self) -> InstallLocation {
+        let mut location = InstallLocation {
+            scope: match self.system_wide.unwrap_or_default() {
+                true => InstallScope::System,
+                false => InstallScope::User,
+            },
+            dir: self.dir.clone(),
+            ..Default::default()
+        };
+
+        if let Some(subfolder) = &self.subfolder {
+            location.subfolder = subfolder.clone();
+        }
+
+        for (format, dir) in [
+            (PluginFormat::Clap, &self.clap_dir),
+            (PluginFormat::Vst3, &self.vst3_dir),
+            (PluginFormat::Auv2, &self.auv2_dir),
+        ] {
+            if let Some(dir) = dir {
+                location.format_dirs.insert(format, dir.clone());
+            }
+        }
+
+        location
+    }
+}
+
+impl Args {
+    /// Fills in everything that wasn't explicitly specified on the command line from the preset.
+    pub fn with_preset(mut self, preset: &Preset) -> Result<Self> {
+        if self.build.packages.is_empty() {
+            self.build.packages = preset.packages.clone().unwrap_or_default();
+        }
+
+        if self.build.profile.is_none() {
+            self.build.profile = preset.profile.clone();
+        }
+
+        if self.build.target.is_empty() {
+            self.build.target = preset
+                .targets
+                .iter()
+                .flatten()
+                .map(|x| x.to_string())
+                .collect();
+        }
+
+        if self.build.features.is_empty() {
+            self.build.features = preset.features.clone().unwrap_or_default();
+        }
+
+        self.build.all_features = self.build.all_features.or(preset.all_features);
+        self.build.no_default_features = self
+            .build
+            .no_default_features
+            .or(preset.no_default_features);
+        self.build.install_targets = self.build.install_targets.or(preset.install_targets);
+        self.install = self.install.or(preset.install);
+        self.install_location = self.install_location.with_preset(preset);
+        self.validate = self.validate.or(preset.validate);
+        self.validator_strictness = self.validator_strictness.or(preset.validator_strictness);
+
+        if self.validator.is_empty() {
+            self.validator = preset
+                .validator
+                .iter()
+                .flatten()
+                .map(|x| x.to_string())
+                .collect();
+        }
+
+        if self.output_dir.is_none() {
+            self.output_dir = preset.output_dir.clone();
+        }
+
+        if let (None, Some(sign)) = (&self.codesign, &preset.sign) {
+            let password = std::env::var(&sign.password_env).map_err(|_| {
+                Error::new(format!(
+                    "environment variable {} is not set",
+                    sign.password_env.bold()
+                ))
+                .with_note("the preset reads the signing password from this variable")
+            })?;
+
+            self.codesign = Some(ArgsAppleSign {
+                identity: sign.identity.clone(),
+                team: sign.team.clone(),
+                username: sign.username.clone(),
+                password,
+            });
+        }
+
+        Ok(self)
+    }
+}
+
+/// A `--flag` and its `--negation`, `None` if neither is given so a preset can fill it in.
+fn toggle(
+    flag: &'static str,
+    help: &'static str,
+    negation: &'static str,
+    negation_help: &'static str,
+) -> impl Parser<Option<bool>> {
+    let on = bpaf::long(flag).help(help).req_flag(true);
+    let off = bpaf::long(negation).help(negation_help).req_flag(false);
+    construct!([on, off]).optional()
+}
+
 fn parser_build() -> impl Parser<ArgsBuild> {
     let packages = bpaf::long("package")
         .short('p')
@@ -80,12 +259,34 @@ fn parser_build() -> impl Parser<ArgsBuild> {
         .help("List of features to use")
         .many();
 
-    let all_features = bpaf::long("all-features")
-        .switch()
-        .help("Use all available features");
-    let no_default_features = bpaf::long("no-default-features")
-        .switch()
-        .help("Do not use the default features");
+    let all_features = toggle(
+        "all-features",
+        "Use all available features",
+        "no-all-features",
+        "Don't use all features, even if the preset does",
+    );
+    let no_default_features = toggle(
+        "no-default-features",
+        "Do not use the default features",
+        "default-features",
+        "Use the default features, even if the preset doesn't",
+    );
+
+    let jobs = bpaf::long("jobs")
+        .short('j')
+        .argument::<usize>("N")
+        .help(
+            "Build at most N packages and targets at the same time (the number of CPUs by default)",
+        )
+        .guard(|x| *x > 0, "the number of jobs must be at least 1")
+        .optional();
+
+    let install_targets = toggle(
+        "install-targets",
+        "Install missing rust targets with rustup",
+        "no-install-targets",
+        "Fail on missing rust targets, even if the preset installs them",
+    );
 
     construct!(ArgsBuild {
         packages,
@@ -94,6 +295,8 @@ fn parser_build() -> impl Parser<ArgsBuild> {
         features,
         all_features,
         no_default_features,
+        jobs,
+        install_targets,
     })
 }
 
@@ -122,16 +325,101 @@ fn parser_codesign() -> impl Parser<ArgsAppleSign> {
     })
 }
 
+fn parser_install_location() -> impl Parser<ArgsInstallLocation> {
+    let dir = bpaf::long("install-dir")
+        .argument("DIR")
+        .help("Install all formats into this directory instead of the system plugin folders")
+        .optional();
+    let clap_dir = bpaf::long("install-dir-clap")
+        .argument("DIR")
+        .help("Install CLAP plugins into this directory")
+        .optional();
+    let vst3_dir = bpaf::long("install-dir-vst3")
+        .argument("DIR")
+        .help("Install VST3 plugins into this directory")
+        .optional();
+    let auv2_dir = bpaf::long("install-dir-auv2")
+        .argument("DIR")
+        .help("Install AUv2 plugins into this directory")
+        .optional();
+
+    let system_wide = toggle(
+        "system-wide",
+        "Use the plugin folders shared by all users",
+        "no-system-wide",
+        "Use the per-user plugin folders, even if the preset is system wide",
+    );
+    let subfolder = bpaf::long("install-subfolder")
+        .argument("NAME")
+        .help("Subfolder of the plugin folders to install into (dev by default, empty for none)")
+        .optional();
+
+    construct!(ArgsInstallLocation {
+        dir,
+        clap_dir,
+        vst3_dir,
+        auv2_dir,
+        system_wide,
+        subfolder,
+    })
+}
+
 fn parser_args() -> impl Parser<Args> {
     let build = parser_build();
 
-    let install = bpaf::long("install")
-        .switch()
-        .help("Install built plugins to system locations");
+    let preset = bpaf::long("preset")
+        .argument("PRESET")
+        .help("Use the named preset from picobundler.toml")
+        .optional();
+    let output_dir = bpaf::long("output-dir")
+        .argument("DIR")
+        .help("Copy the bundles to this directory (target/bundled by default)")
+        .optional();
+
+    let install_mode = bpaf::long("install")
+        .help("Install built plugins to system locations, --install=symlink links to the bundles")
+        .argument::<String>("MODE")
+        .adjacent()
+        .parse(|x| {
+            x.parse::<InstallMode>()
+                .map_err(|e| e.message().to_string())
+        });
+    let install_copy = bpaf::long("install").req_flag(InstallMode::Copy).hide();
+    let install = construct!([install_mode, install_copy]).optional();
+    let install_location = parser_install_location();
+    let validate = toggle(
+        "validate",
+        "Load the built CLAP plugins and run them through a smoke test",
+        "no-validate",
+        "Skip the smoke test, even if the preset runs it",
+    );
+    let validator = bpaf::long("validator")
+        .argument("VALIDATOR")
+        .help("Run pluginval or clap-validator on the bundles")
+        .many();
+    let validator_strictness = bpaf::long("validator-strictness")
+        .argument::<u32>("LEVEL")
+        .help("The strictness level (1 to 10) pluginval runs at, pluginval's default otherwise")
+        .guard(
+            |x| (1..=10).contains(x),
+            "the strictness level must be from 1 to 10",
+        )
+        .optional();
     let verbose = bpaf::long("verbose")
         .short('v')
         .switch()
         .help("Enable verbose logging");
+    let message_format = bpaf::long("message-format")
+        .help("Also print JSON events for spans, warnings, artifacts and errors to stdout (human or json)")
+        .argument::<String>("FORMAT")
+        .parse(|x| {
+            x.parse::<MessageFormat>()
+                .map_err(|e| e.message().to_string())
+        })
+        .fallback(MessageFormat::Human);
+    let timings = bpaf::long("timings")
+        .switch()
+        .help("Write a Chrome trace and a summary of the slowest steps to <output-dir>/timings");
 
     let vst3 = bpaf::long("vst3")
         .argument("SDK")
@@ -145,9 +433,17 @@ fn parser_args() -> impl Parser<Args> {
     let clap = bpaf::long("clap").switch().help("Build CLAP plugin");
 
     construct!(Args {
+        preset,
+        output_dir,
         install,
+        install_location,
+        validate,
+        validator,
+        validator_strictness,
         build,
         verbose,
+        message_format,
+        timings,
         codesign,
         vst3,
         auv2,
@@ -155,6 +451,114 @@ fn parser_args() -> impl Parser<Args> {
     })
 }
 
-pub fn parse_args() -> Args {
-    parser_args().to_options().run()
+fn parser_inspect() -> impl Parser<ArgsInspect> {
+    let bundle = bpaf::positional("BUNDLE").help("The .clap bundle to inspect");
+    construct!(ArgsInspect { bundle })
+}
+
+fn parser_uninstall() -> impl Parser<ArgsUninstall> {
+    let packages = bpaf::long("package")
+        .short('p')
+        .argument("PACKAGE")
+        .help("A list of packages to uninstall")
+        .many();
+
+    let all = bpaf::long("all")
+        .switch()
+        .help("Uninstall every plugin installed by picobundler");
+
+    let install_location = parser_install_location();
+
+    construct!(ArgsUninstall {
+        packages,
+        all,
+        install_location
+    })
+}
+
+fn parser_doctor() -> impl Parser<ArgsDoctor> {
+    let target = bpaf::long("target")
+        .argument("TARGET")
+        .help("The targets to check, defaults to the host")
+        .many();
+    let vst3 = bpaf::long("vst3")
+        .argument("SDK")
+        .adjacent()
+        .help("Check the tools for VST3 plugins")
+        .fallback(ArgsVst3::None);
+    let auv2 = bpaf::long("auv2")
+        .switch()
+        .help("Check the tools for AUv2 plugins");
+    let sign = bpaf::long("sign")
+        .switch()
+        .help("Check the tools for signing and notarizing apple bundles");
+
+    construct!(ArgsDoctor {
+        target,
+        vst3,
+        auv2,
+        sign
+    })
+}
+
+fn parser_zig_cc() -> impl Parser<ArgsZigCc> {
+    let zig_triple = bpaf::positional("ZIG_TRIPLE");
+    let args = bpaf::any("ARG", Some).many();
+    construct!(ArgsZigCc { zig_triple, args })
+}
+
+fn parser_command() -> impl Parser<ArgsCommand> {
+    let inspect = parser_inspect()
+        .map(ArgsCommand::Inspect)
+        .to_options()
+        .descr("Print the plugin descriptors of a built CLAP plugin")
+        .command("inspect");
+
+    let watch = parser_args()
+        .map(ArgsCommand::Watch)
+        .to_options()
+        .descr("Rebuild the packages for the host whenever their sources change")
+        .command("watch");
+
+    let uninstall = parser_uninstall()
+        .map(ArgsCommand::Uninstall)
+        .to_options()
+        .descr("Remove plugins installed with --install")
+        .command("uninstall");
+
+    let doctor = parser_doctor()
+        .map(ArgsCommand::Doctor)
+        .to_options()
+        .descr("Check that the tools needed for the given formats and targets are installed")
+        .command("doctor");
+
+    // spawned by `--validate`, so a crashing plugin only takes down the child process
+    let validate_clap = parser_inspect()
+        .map(ArgsCommand::ValidateClap)
+        .to_options()
+        .command("validate-clap")
+        .hide();
+
+    // the linker of cross compiled cdylibs, see `zig_linker` in build/zig.rs
+    let zig_cc = parser_zig_cc()
+        .map(ArgsCommand::ZigCc)
+        .to_options()
+        .command("zig-cc")
+        .hide();
+
+    let bundle = parser_args().map(ArgsCommand::Bundle);
+
+    construct!([
+        inspect,
+        watch,
+        uninstall,
+        doctor,
+        validate_clap,
+        zig_cc,
+        bundle
+    ])
+}
+
+pub fn parse_args() -> ArgsCommand {
+    parser_command().to_options().run()
 }
@@ -0,0 +1,721 @@
+use super::{
+    BuildArtifact, BuildTarget, PluginFormat,
+    elf::{ElfFile, ElfVersionNeed},
+    macho::MachOFile,
+    pe::PeFile,
+};
+use crate::{
+    cli::{Error, Result, report_message, report_warning},
+    report_span,
+};
+use owo_colors::OwoColorize;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use target_lexicon::OperatingSystem;
+
+/// How many of the symbols that need a too new version are listed.
+const LISTED_SYMBOLS: usize = 5;
+
+/// The entry points of the formats and the clap-wrapper, exported on purpose.
+const ENTRY_POINTS: &[&str] = &[
+    "clap_entry",
+    "GetPluginFactory",
+    "ModuleEntry",
+    "ModuleExit",
+    "bundleEntry",
+    "bundleExit",
+    "InitDll",
+    "ExitDll",
+];
+
+/// Libraries every desktop Linux ships, `*` matches anything.
+const LINUX_LIBRARIES: &[&str] = &[
+    "ld-linux*.so.*",
+    "libc.so.6",
+    "libm.so.6",
+    "libdl.so.2",
+    "libpthread.so.0",
+    "librt.so.1",
+    "libgcc_s.so.1",
+    "libX11.so.6",
+    "libX11-xcb.so.1",
+    "libXcursor.so.1",
+    "libXrandr.so.2",
+    "libxcb.so.1",
+    "libxcb-*.so.*",
+    "libGL.so.1",
+    "libEGL.so.1",
+];
+
+/// The system libraries and frameworks of macOS, not Homebrew's `/usr/local` or `/opt/homebrew`.
+const MACOS_LIBRARIES: &[&str] = &["/usr/lib/*", "/System/Library/*"];
+
+/// DLLs that are part of Windows, compared case insensitively. The Visual C++ runtime isn't, the
+/// wrapper links it statically.
+const WINDOWS_LIBRARIES: &[&str] = &[
+    "api-ms-win-*",
+    "advapi32.dll",
+    "bcrypt.dll",
+    "bcryptprimitives.dll",
+    "comctl32.dll",
+    "comdlg32.dll",
+    "crypt32.dll",
+    "d2d1.dll",
+    "d3d11.dll",
+    "dwmapi.dll",
+    "dwrite.dll",
+    "dxgi.dll",
+    "gdi32.dll",
+    "imm32.dll",
+    "kernel32.dll",
+    "msvcrt.dll",
+    "ntdll.dll",
+    "ole32.dll",
+    "oleaut32.dll",
+    "opengl32.dll",
+    "propsys.dll",
+    "secur32.dll",
+    "shell32.dll",
+    "shlwapi.dll",
+    "ucrtbase.dll",
+    "user32.dll",
+    "userenv.dll",
+    "uxtheme.dll",
+    "version.dll",
+    "winmm.dll",
+    "ws2_32.dll",
+];
+
+/// What happens when a binary links against a library that isn't on the allowlist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownLibraries {
+    #[default]
+    Warn,
+    Deny,
+}
+
+impl FromStr for UnknownLibraries {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "warn" => Ok(Self::Warn),
+            "deny" => Ok(Self::Deny),
+            _ => Err(
+                Error::new(format!("unknown library policy {}", s.bold())).with_note(format!(
+                    "use either {} or {}",
+                    "warn".bold().bright_yellow(),
+                    "deny".bold().bright_red()
+                )),
+            ),
+        }
+    }
+}
+
+/// A shared library the binaries of an artifact load.
+#[derive(Debug, Clone)]
+pub struct LibraryDependency {
+    /// e.g. `libc.so.6`, `/usr/lib/libSystem.B.dylib` or `KERNEL32.dll`
+    pub name: String,
+    /// On the allowlist of the target or of the package
+    pub allowed: bool,
+}
+
+/// The newest glibc and libstdc++ symbol versions the binaries of a Linux artifact need.
+#[derive(Debug, Clone, Default)]
+pub struct GlibcVersions {
+    /// e.g. `2.34` for `GLIBC_2.34`
+    pub glibc: Option<String>,
+    /// e.g. `3.4.29` for `GLIBCXX_3.4.29`
+    pub glibcxx: Option<String>,
+}
+
+/// Reads the symbol versions the binaries of a Linux artifact need from their
+/// `.gnu.version_r` sections. Fails if they need a newer glibc than the target is pinned to or
+/// than `min_glibc`, the oldest glibc the package supports.
+pub fn audit_glibc_versions(
+    artifact: &BuildArtifact,
+    min_glibc: Option<&str>,
+) -> Result<Option<GlibcVersions>> {
+    if !matches!(artifact.target.operating_system(), OperatingSystem::Linux) {
+        return Ok(None);
+    }
+
+    report_span!(
+        "auditing the glibc versions of {}",
+        artifact.path.display().bold()
+    );
+
+    let pinned = match &artifact.target {
+        BuildTarget::TripleGlibc(_, glibc) => Some(glibc.as_str()),
+        _ => None,
+    };
+
+    let limit = glibc_limit(pinned, min_glibc);
+
+    let mut versions = GlibcVersions::default();
+    for binary in bundle_binaries(&artifact.path)? {
+        let data = std::fs::read(&binary)?;
+        if !ElfFile::is_elf(&data) {
+            continue;
+        }
+
+        let needs = ElfFile::parse(&data)?.version_needs()?;
+        let glibc = sorted_versions(&needs, "GLIBC_");
+        let glibcxx = sorted_versions(&needs, "GLIBCXX_");
+
+        report_message!(
+            "{} needs {}",
+            binary.display(),
+            [glibc.first(), glibcxx.first()]
+                .into_iter()
+                .flatten()
+                .map(|x| x.2.version.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        versions.glibc = newer(versions.glibc, glibc.first().map(|x| x.1));
+        versions.glibcxx = newer(versions.glibcxx, glibcxx.first().map(|x| x.1));
+
+        let Some((limit, reason)) = &limit else {
+            continue;
+        };
+
+        let allowed = parse_version(limit);
+        let too_new = glibc
+            .iter()
+            .filter(|x| x.0 > allowed)
+            .map(|x| x.2)
+            .collect::<Vec<_>>();
+
+        let Some(newest) = too_new.first() else {
+            continue;
+        };
+
+        let mut error = Error::new(format!(
+            "{} needs {} but {}",
+            binary.display().bold(),
+            newest.version.bold(),
+            reason
+        ));
+
+        for need in &too_new {
+            let mut symbols = need.symbols.clone();
+            symbols.sort();
+            let more = symbols.len().saturating_sub(LISTED_SYMBOLS);
+            symbols.truncate(LISTED_SYMBOLS);
+
+            error = error.with_note(format!(
+                "{} from {} is needed by {}{}",
+                need.version,
+                need.library,
+                symbols.join(", "),
+                match more {
+                    0 => String::new(),
+                    more => format!(" and {} more", more),
+                }
+            ));
+        }
+
+        let hint = match (&artifact.target, pinned) {
+            (BuildTarget::Triple(triple), None) => format!(
+                "build for {} to link against glibc {} with zig",
+                format!("{}.{}", triple, limit).bold(),
+                limit
+            ),
+            _ => "these usually come from C code or prebuilt libraries built against the glibc \
+                  of this machine"
+                .to_string(),
+        };
+
+        return Err(error.with_note(hint));
+    }
+
+    Ok(Some(versions))
+}
+
+/// The newest glibc the binaries may need and why, the older of the version the target is pinned
+/// to and `min_glibc` since both have to work.
+fn glibc_limit<'a>(
+    pinned: Option<&'a str>,
+    min_glibc: Option<&'a str>,
+) -> Option<(&'a str, String)> {
+    [
+        pinned.map(|x| (x, format!("the target is pinned to glibc {}", x.bold()))),
+        min_glibc.map(|x| (x, format!("{} is set to {}", "min-glibc".bold(), x.bold()))),
+    ]
+    .into_iter()
+    .flatten()
+    .min_by_key(|x| parse_version(x.0))
+}
+
+/// Lists the shared libraries the binaries of an artifact load and checks them against the
+/// libraries of the target's operating system and `allowed`, the package's additions.
+pub fn audit_libraries(
+    artifact: &BuildArtifact,
+    allowed: &[String],
+    unknown: UnknownLibraries,
+) -> Result<Vec<LibraryDependency>> {
+    let (system, ignore_case) = match artifact.target.operating_system() {
+        OperatingSystem::Linux => (LINUX_LIBRARIES, false),
+        OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_) => (MACOS_LIBRARIES, false),
+        OperatingSystem::Windows => (WINDOWS_LIBRARIES, true),
+        _ => return Ok(vec![]),
+    };
+
+    report_span!(
+        "auditing the libraries of {}",
+        artifact.path.display().bold()
+    );
+
+    let is_allowed = |name: &str| {
+        let matches = |pattern: &str| match ignore_case {
+            true => matches_pattern(&pattern.to_lowercase(), &name.to_lowercase()),
+            false => matches_pattern(pattern, name),
+        };
+
+        system.iter().any(|x| matches(x)) || allowed.iter().any(|x| matches(x))
+    };
+
+    let mut libraries = Vec::<LibraryDependency>::new();
+    let mut error = None::<Error>;
+    for binary in bundle_binaries(&artifact.path)? {
+        let data = std::fs::read(&binary)?;
+        let mut names = binary_libraries(&data)?;
+        names.sort();
+        names.dedup();
+
+        let unexpected = names
+            .iter()
+            .filter(|x| !is_allowed(x))
+            .map(|x| x.as_str())
+            .collect::<Vec<_>>();
+
+        if !unexpected.is_empty() {
+            match unknown {
+                UnknownLibraries::Warn => {
+                    report_warning!(
+                        "{} links against {}, which may be missing on other machines",
+                        binary.display(),
+                        unexpected.join(", ")
+                    );
+                }
+                UnknownLibraries::Deny => {
+                    error = Some(
+                        error
+                            .unwrap_or_else(|| {
+                                Error::new(format!(
+                                    "{} links against libraries that may be missing on other \
+                                     machines",
+                                    artifact.path.display().bold()
+                                ))
+                            })
+                            .with_note(format!(
+                                "{} needs {}",
+                                binary.display(),
+                                unexpected.join(", ")
+                            )),
+                    )
+                }
+            }
+        }
+
+        for name in names {
+            if !libraries.iter().any(|x| x.name == name) {
+                libraries.push(LibraryDependency {
+                    allowed: is_allowed(&name),
+                    name,
+                });
+            }
+        }
+    }
+
+    if let Some(error) = error {
+        return Err(error.with_note(format!(
+            "link them statically, or add them to {} if every user has them",
+            "allowed-libraries".bold()
+        )));
+    }
+
+    libraries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(libraries)
+}
+
+/// The `DT_NEEDED` entries, PE imports or Mach-O dylibs of every slice of a binary.
+fn binary_libraries(data: &[u8]) -> Result<Vec<String>> {
+    if ElfFile::is_elf(data) {
+        ElfFile::parse(data)?.needed()
+    } else if PeFile::is_pe(data) {
+        PeFile::parse(data)?.imports()
+    } else if MachOFile::is_macho(data) {
+        let mut dylibs = Vec::new();
+        for slice in MachOFile::parse(data)? {
+            dylibs.extend(slice.dylibs()?);
+        }
+        Ok(dylibs)
+    } else {
+        Ok(vec![])
+    }
+}
+
+/// Matches `name` against a pattern where `*` stands for any text.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Checks that every binary of an artifact exports the entry point hosts look up for its format,
+/// and warns about mangled Rust and C++ symbols that are exported by accident.
+pub fn audit_exports(artifact: &BuildArtifact) -> Result<()> {
+    report_span!("auditing the exports of {}", artifact.path.display().bold());
+
+    let required = match artifact.format {
+        PluginFormat::Clap => vec!["clap_entry".to_string()],
+        PluginFormat::Vst3 => vec!["GetPluginFactory".to_string()],
+        PluginFormat::Auv2 => audio_unit_factories(&artifact.path)?,
+    };
+
+    for binary in bundle_binaries(&artifact.path)? {
+        let data = std::fs::read(&binary)?;
+        for exports in binary_exports(&data)? {
+            if let Some(missing) = required.iter().find(|x| !exports.contains(x)) {
+                return Err(Error::new(format!(
+                    "{} doesn't export {}",
+                    binary.display().bold(),
+                    missing.bold()
+                ))
+                .with_note(format!(
+                    "hosts look up {} to load the plugin, without it the plugin can't be loaded",
+                    missing
+                ))
+                .with_note(match artifact.format {
+                    PluginFormat::Clap => format!(
+                        "the crate has to export {} unmangled, e.g. with nih_plug's \
+                         `nih_export_clap!`",
+                        missing
+                    ),
+                    _ => "the entry point comes from the clap-wrapper, its build may have \
+                          dropped `clap_entry` of the static library"
+                        .to_string(),
+                }));
+            }
+
+            let mut mangled = exports
+                .iter()
+                .filter(|x| !ENTRY_POINTS.contains(&x.as_str()) && is_mangled(x))
+                .collect::<Vec<_>>();
+
+            if mangled.is_empty() {
+                continue;
+            }
+
+            let count = mangled.len();
+            mangled.sort();
+            mangled.truncate(LISTED_SYMBOLS);
+
+            report_warning!(
+                "{} exports {} mangled rust or c++ symbols, e.g. {}",
+                binary.display(),
+                count,
+                mangled
+                    .iter()
+                    .map(|x| x.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// The exported symbols of a binary, one list per architecture of a universal binary.
+fn binary_exports(data: &[u8]) -> Result<Vec<Vec<String>>> {
+    if ElfFile::is_elf(data) {
+        Ok(vec![ElfFile::parse(data)?.exports()?])
+    } else if PeFile::is_pe(data) {
+        Ok(vec![PeFile::parse(data)?.exports()?])
+    } else if MachOFile::is_macho(data) {
+        MachOFile::parse(data)?
+            .iter()
+            .map(|x| x.exports())
+            .collect()
+    } else {
+        Ok(vec![])
+    }
+}
+
+/// The `factoryFunction`s of the audio components in the `Info.plist` of an AUv2 bundle.
+fn audio_unit_factories(bundle: &Path) -> Result<Vec<String>> {
+    let plist = bundle.join("Contents").join("Info.plist");
+    if !plist.is_file() {
+        return Ok(vec![]);
+    }
+
+    Ok(plist_factories(&std::fs::read_to_string(plist)?))
+}
+
+fn plist_factories(plist: &str) -> Vec<String> {
+    let mut factories = plist
+        .split("<key>factoryFunction</key>")
+        .skip(1)
+        .filter_map(|x| {
+            let value = x.trim_start().strip_prefix("<string>")?;
+            Some(value[..value.find("</string>")?].trim().to_string())
+        })
+        .collect::<Vec<_>>();
+
+    factories.dedup();
+    factories
+}
+
+/// Itanium (`_ZN`), Rust v0 (`_R`) or MSVC (`?`) mangling.
+fn is_mangled(symbol: &str) -> bool {
+    symbol.starts_with("_Z") || symbol.starts_with("_R") || symbol.starts_with('?')
+}
+
+/// The needed versions with `prefix` as numbers and text without the prefix, newest first.
+fn sorted_versions<'a>(
+    needs: &'a [ElfVersionNeed],
+    prefix: &str,
+) -> Vec<(Vec<u32>, &'a str, &'a ElfVersionNeed)> {
+    let mut versions = needs
+        .iter()
+        .filter_map(|need| {
+            let text = need.version.strip_prefix(prefix)?;
+            let version = parse_version(text);
+            (!version.is_empty()).then_some((version, text, need))
+        })
+        .collect::<Vec<_>>();
+
+    versions.sort_by(|a, b| b.0.cmp(&a.0));
+    versions
+}
+
+fn newer(current: Option<String>, version: Option<&str>) -> Option<String> {
+    match (current, version) {
+        (Some(current), Some(version)) if parse_version(version) <= parse_version(&current) => {
+            Some(current)
+        }
+        (current, None) => current,
+        (_, Some(version)) => Some(version.to_string()),
+    }
+}
+
+/// The native binaries in a bundle, or the file itself if it isn't a folder. Links are skipped,
+/// like the `Versions/Current` of a framework, which would list its binaries twice.
+pub fn bundle_binaries(bundle: &Path) -> Result<Vec<PathBuf>> {
+    if !bundle.is_dir() {
+        return Ok(vec![bundle.to_path_buf()]);
+    }
+
+    let mut binaries = Vec::new();
+    let mut dirs = vec![bundle.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                continue;
+            }
+
+            let path = entry.path();
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if is_binary(&path) {
+                binaries.push(path);
+            }
+        }
+    }
+
+    binaries.sort();
+    Ok(binaries)
+}
+
+/// Only looks at the start of the file, which holds the PE signature of any binary linkers write.
+fn is_binary(path: &Path) -> bool {
+    use std::io::Read;
+
+    let mut header = Vec::new();
+    let read = std::fs::File::open(path).and_then(|x| x.take(4096).read_to_end(&mut header));
+
+    read.is_ok()
+        && (ElfFile::is_elf(&header) || PeFile::is_pe(&header) || MachOFile::is_macho(&header))
+}
+
+/// `2.28` or `2.3.4` as numbers, so `2.30` sorts after `2.4`.
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split('.')
+        .map(|x| x.parse().ok())
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glibc_versions() {
+        assert_eq!(parse_version("2.30"), vec![2, 30]);
+        assert_eq!(parse_version("3.4.29"), vec![3, 4, 29]);
+        assert_eq!(parse_version("PRIVATE"), Vec::<u32>::new());
+        assert!(parse_version("2.30") > parse_version("2.4"));
+
+        assert_eq!(
+            newer(Some("2.4".into()), Some("2.30")).as_deref(),
+            Some("2.30")
+        );
+        assert_eq!(
+            newer(Some("2.30".into()), Some("2.4")).as_deref(),
+            Some("2.30")
+        );
+        assert_eq!(newer(Some("2.17".into()), None).as_deref(), Some("2.17"));
+        assert_eq!(newer(None, Some("2.17")).as_deref(), Some("2.17"));
+
+        let need = |version: &str| ElfVersionNeed {
+            library: "libc.so.6".to_string(),
+            version: version.to_string(),
+            symbols: vec![],
+        };
+        let needs = [
+            need("GLIBC_2.4"),
+            need("GLIBC_2.30"),
+            need("GLIBC_PRIVATE"),
+            need("GLIBCXX_3.4.29"),
+            need("GLIBC_2.2.5"),
+        ];
+        let sorted = |prefix| {
+            sorted_versions(&needs, prefix)
+                .into_iter()
+                .map(|x| x.1)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted("GLIBC_"), ["2.30", "2.4", "2.2.5"]);
+        assert_eq!(sorted("GLIBCXX_"), ["3.4.29"]);
+    }
+
+    #[test]
+    fn test_glibc_limit() {
+        let limit = |pinned, min_glibc| glibc_limit(pinned, min_glibc).map(|x| x.0);
+
+        assert_eq!(limit(Some("2.30"), Some("2.4")), Some("2.4"));
+        assert_eq!(limit(Some("2.4"), Some("2.30")), Some("2.4"));
+        assert_eq!(limit(Some("2.28"), None), Some("2.28"));
+        assert_eq!(limit(None, Some("2.31")), Some("2.31"));
+        assert_eq!(limit(None, None), None);
+
+        let (_, reason) = glibc_limit(Some("2.17"), Some("2.28")).unwrap();
+        assert!(reason.starts_with("the target is pinned"));
+    }
+
+    #[test]
+    fn test_is_mangled() {
+        assert!(is_mangled("_ZN6plugin7processEv"));
+        assert!(is_mangled("_RNvCs1234_6plugin7process"));
+        assert!(is_mangled("?process@Plugin@@QEAAXXZ"));
+        assert!(!is_mangled("clap_entry"));
+        assert!(!is_mangled("GetPluginFactory"));
+        assert!(!is_mangled("_clap_entry"));
+    }
+
+    #[test]
+    fn test_plist_factories() {
+        let plist = r#"
+            <dict>
+                <key>AudioComponents</key>
+                <array>
+                    <dict>
+                        <key>factoryFunction</key>
+                        <string>GainFactory</string>
+                        <key>name</key>
+                        <string>Vendor: Gain</string>
+                    </dict>
+                    <dict>
+                        <key>factoryFunction</key>
+                        <string>GainFactory</string>
+                    </dict>
+                    <dict>
+                        <key>factoryFunction</key>
+                        <string> SynthFactory </string>
+                    </dict>
+                </array>
+            </dict>
+        "#;
+
+        assert_eq!(plist_factories(plist), ["GainFactory", "SynthFactory"]);
+        assert_eq!(plist_factories("<dict></dict>"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("ld-linux*.so.*", "ld-linux-x86-64.so.2"));
+        assert!(matches_pattern("ld-linux*.so.*", "ld-linux.so.2"));
+        assert!(!matches_pattern("ld-linux*.so.*", "ld-linux-x86-64.so"));
+        assert!(!matches_pattern("ld-linux*.so.*", "libld-linux.so.2"));
+
+        assert!(matches_pattern("/usr/lib/*", "/usr/lib/libSystem.B.dylib"));
+        assert!(matches_pattern("/usr/lib/*", "/usr/lib/"));
+        assert!(!matches_pattern(
+            "/usr/lib/*",
+            "/usr/local/lib/libasound.2.dylib"
+        ));
+
+        assert!(matches_pattern(
+            "api-ms-win-*",
+            "api-ms-win-crt-runtime-l1-1-0.dll"
+        ));
+        assert!(!matches_pattern(
+            "api-ms-win-*",
+            "ext-ms-win-ntuser-l1-1-0.dll"
+        ));
+
+        assert!(matches_pattern("libc.so.6", "libc.so.6"));
+        assert!(!matches_pattern("libc.so.6", "libc.so.60"));
+        assert!(!matches_pattern("libc.so.6", "libc.so"));
+
+        assert!(matches_pattern("*", "libasound.so.2"));
+        assert!(matches_pattern("lib*.so.*", "libxcb-shm.so.0"));
+        assert!(!matches_pattern("a*a", "a"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bundle_binaries_links() {
+        let bundle = std::env::temp_dir().join(format!("picobundler-audit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&bundle);
+
+        // a framework with `Versions/Current` linking to `A`, and a link back to the bundle
+        let versions = bundle.join("Contents/Frameworks/Dep.framework/Versions");
+        std::fs::create_dir_all(versions.join("A")).unwrap();
+        std::fs::write(versions.join("A/Dep"), b"\x7fELF\x02\x01").unwrap();
+        std::fs::write(versions.join("A/Info.plist"), b"<plist/>").unwrap();
+        std::os::unix::fs::symlink("A", versions.join("Current")).unwrap();
+        std::os::unix::fs::symlink(&bundle, bundle.join("Contents/loop")).unwrap();
+
+        assert_eq!(bundle_binaries(&bundle).unwrap(), [versions.join("A/Dep")]);
+        let _ = std::fs::remove_dir_all(&bundle);
+    }
+}
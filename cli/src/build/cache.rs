@@ -1,4 +1,4 @@
-use crate::cli::{Command, Error, Result, report_message, report_span};
+use crate::cli::{Command, Error, Result, remove_on_interrupt, report_message, report_span};
 use owo_colors::OwoColorize;
 use std::path::{Path, PathBuf};
 
@@ -57,6 +57,7 @@ impl DependencyCache {
             std::fs::remove_dir_all(&tmp_folder)?;
         }
 
+        let _cleanup = remove_on_interrupt(&tmp_folder);
         std::fs::create_dir_all(&tmp_folder)?;
 
         report_message!("downloading dependency {}", item.print_name());
@@ -1,5 +1,6 @@
+use super::CmakeBuildType;
 use crate::{
-    cli::{Command, Error, Result, report_message},
+    cli::{Command, Error, LOG_ENV, Result, log_path, report_message, report_warning, write_log},
     report_span,
 };
 use owo_colors::OwoColorize;
@@ -9,7 +10,7 @@ use std::{
     path::{Path, PathBuf},
     str::FromStr,
 };
-use target_lexicon::{Environment, OperatingSystem, Triple};
+use target_lexicon::Triple;
 use tinyjson::JsonValue;
 
 #[derive(Debug, Copy, Clone)]
@@ -18,6 +19,37 @@ pub enum CargoCrateType {
     Staticlib,
 }
 
+impl CargoCrateType {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Cdylib => "cdylib",
+            Self::Staticlib => "staticlib",
+        }
+    }
+
+    /// Picks the library itself out of the files cargo reported for a target,
+    /// skipping import libraries, debug info and the like.
+    fn select_output(&self, crate_types: &[String], filenames: &[String]) -> Option<PathBuf> {
+        if !crate_types.iter().any(|x| x == self.name()) {
+            return None;
+        }
+
+        let extensions: &[&str] = match self {
+            Self::Cdylib => &["so", "dylib", "dll"],
+            Self::Staticlib => &["a", "lib"],
+        };
+
+        filenames.iter().map(PathBuf::from).find(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            extensions.iter().any(|ext| {
+                path.extension().is_some_and(|x| x == *ext)
+                    && !name.ends_with(".dll.lib")
+                    && !name.ends_with(".dll.a")
+            })
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CargoBuild {
     pub crate_type: CargoCrateType,
@@ -28,6 +60,8 @@ pub struct CargoBuild {
     pub features: Vec<String>,
     pub all_features: bool,
     pub no_default_features: bool,
+    /// Replaces the default linker of the target, e.g. with zig for cross compilation
+    pub linker: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -35,6 +69,48 @@ pub struct CargoArtifact {
     pub package: String,
     pub path: PathBuf,
     pub native_static_libs: Option<String>,
+    /// The profile cargo reports it was built with
+    pub profile: Option<CargoProfile>,
+}
+
+/// The `profile` of a `compiler-artifact` message. cargo has already applied the overrides from
+/// `.cargo/config.toml` and the `CARGO_PROFILE_<NAME>_*` variables to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoProfile {
+    pub opt_level: String,
+    pub debug: bool,
+}
+
+impl CargoProfile {
+    fn from_json(value: &JsonValue) -> Option<Self> {
+        let opt_level = value
+            .get::<HashMap<String, JsonValue>>()?
+            .get("opt_level")?;
+
+        // `debuginfo` is 0 to 2 or the name of a level, `line-directives-only` only emits
+        // directives for profilers and no debug info a debugger could use
+        let debug = match value.get::<HashMap<String, JsonValue>>()?.get("debuginfo") {
+            Some(JsonValue::Number(level)) => *level > 0.0,
+            Some(JsonValue::String(level)) => {
+                !matches!(level.as_str(), "none" | "line-directives-only")
+            }
+            _ => false,
+        };
+
+        Some(Self {
+            opt_level: opt_level.get::<String>()?.clone(),
+            debug,
+        })
+    }
+
+    pub fn cmake_build_type(&self) -> CmakeBuildType {
+        match (self.opt_level.as_str(), self.debug) {
+            ("0", _) => CmakeBuildType::Debug,
+            (_, true) => CmakeBuildType::RelWithDebInfo,
+            ("s" | "z", false) => CmakeBuildType::MinSizeRel,
+            (_, false) => CmakeBuildType::Release,
+        }
+    }
 }
 
 pub fn cargo_build(build: CargoBuild) -> Result<Vec<CargoArtifact>> {
@@ -67,6 +143,16 @@ pub fn cargo_build(build: CargoBuild) -> Result<Vec<CargoArtifact>> {
         command = command.arg("--no-default-features");
     }
 
+    if let Some(linker) = &build.linker {
+        let target = build.target.to_string().to_uppercase().replace('-', "_");
+        command = command.env(format!("CARGO_TARGET_{}_LINKER", target), linker);
+
+        // the linker is another picobundler process, which logs its zig command here too
+        if let Some(log) = log_path() {
+            command = command.env(LOG_ENV, log);
+        }
+    }
+
     match build.crate_type {
         CargoCrateType::Cdylib => {
             command = command.arg("--crate-type=cdylib");
@@ -80,6 +166,7 @@ pub fn cargo_build(build: CargoBuild) -> Result<Vec<CargoArtifact>> {
     let mut compiler_messages = Vec::new();
     let mut native_static_libs = HashMap::new();
     let mut link_paths = Vec::new();
+    let mut output_paths = HashMap::new();
 
     command
         .run_stdout_stderr(
@@ -87,7 +174,27 @@ pub fn cargo_build(build: CargoBuild) -> Result<Vec<CargoArtifact>> {
                 Ok(CargoMessage::NativeStaticLibs { package, libs }) => {
                     native_static_libs.insert(package, libs);
                 }
-                Ok(CargoMessage::CompilerMessage { rendered, .. }) => {
+                Ok(CargoMessage::CompilerArtifact {
+                    package,
+                    crate_types,
+                    filenames,
+                    profile,
+                }) => {
+                    if let Some(path) = build.crate_type.select_output(&crate_types, &filenames) {
+                        output_paths.insert(package, (path, profile));
+                    }
+                }
+                Ok(CargoMessage::CompilerMessage {
+                    rendered,
+                    message,
+                    package,
+                    warning,
+                }) => {
+                    if warning {
+                        report_warning!("{}: {}", package, message);
+                    }
+
+                    write_log(&rendered);
                     compiler_messages.push(rendered);
                 }
                 Ok(CargoMessage::BuildScriptOutput { linked_paths, .. }) => {
@@ -115,14 +222,18 @@ pub fn cargo_build(build: CargoBuild) -> Result<Vec<CargoArtifact>> {
 
     let mut artifacts = Vec::new();
     for package in build.packages {
-        let native_static_libs = native_static_libs.remove(&cargo_normalize_package_name(&package));
-        let path = cargo_output_path(
-            &build.target_dir,
-            &build.profile,
-            &build.target,
-            &package,
-            build.crate_type,
-        )?;
+        let native_static_libs = native_static_libs.remove(&package);
+        let (path, profile) = output_paths.remove(&package).ok_or_else(|| {
+            Error::new(format!(
+                "cargo did not produce a {} library for {}",
+                build.crate_type.name(),
+                package.bold()
+            ))
+            .with_note(format!(
+                "make sure the package has a {} target",
+                "[lib]".bold()
+            ))
+        })?;
 
         if let Some(ref libs) = native_static_libs {
             report_message!("extracted native static libs for {}: {}", package, libs);
@@ -132,6 +243,7 @@ pub fn cargo_build(build: CargoBuild) -> Result<Vec<CargoArtifact>> {
             package,
             path,
             native_static_libs,
+            profile,
         });
     }
 
@@ -182,6 +294,8 @@ pub fn cargo_metadata() -> Result<HashMap<String, JsonValue>> {
 
     let output = Command::new(&cargo_cmd())
         .arg("metadata")
+        .arg("--no-deps")
+        .arg("--format-version=1")
         .run()
         .map_err(|e| {
             e.with_note(format!(
@@ -200,64 +314,143 @@ pub fn cargo_metadata() -> Result<HashMap<String, JsonValue>> {
     Ok(value)
 }
 
-fn cargo_cmd() -> String {
-    var("CARGO").unwrap_or_else(|_| "cargo".to_string())
+/// Returns the directory of `package` and the directories of its path dependencies, following
+/// path dependencies that are themselves part of the workspace.
+pub fn cargo_source_dirs(package: &str) -> Result<Vec<PathBuf>> {
+    let metadata = cargo_metadata()?;
+    let packages = metadata
+        .get("packages")
+        .and_then(|x| x.get::<Vec<JsonValue>>())
+        .into_iter()
+        .flatten()
+        .filter_map(|x| {
+            let package = x.get::<HashMap<String, JsonValue>>()?;
+            let name = package.get("name")?.get::<String>()?;
+            let dir = Path::new(package.get("manifest_path")?.get::<String>()?).parent()?;
+            let dependencies = package
+                .get("dependencies")?
+                .get::<Vec<JsonValue>>()?
+                .iter()
+                .filter_map(|x| x.get::<HashMap<String, JsonValue>>()?.get("path"))
+                .filter_map(|x| x.get::<String>().map(PathBuf::from))
+                .collect::<Vec<_>>();
+
+            Some((name.as_str(), dir.to_path_buf(), dependencies))
+        })
+        .collect::<Vec<_>>();
+
+    let (_, dir, _) = packages
+        .iter()
+        .find(|(name, _, _)| *name == package)
+        .ok_or_else(|| Error::new(format!("package {} not found", package.bold())))?;
+
+    let mut dirs = vec![dir.clone()];
+    let mut index = 0;
+    while let Some(dir) = dirs.get(index).cloned() {
+        index += 1;
+
+        let Some((_, _, dependencies)) = packages.iter().find(|(_, x, _)| *x == dir) else {
+            continue;
+        };
+
+        for dependency in dependencies {
+            if !dirs.contains(dependency) {
+                dirs.push(dependency.clone());
+            }
+        }
+    }
+
+    Ok(dirs)
 }
 
-fn cargo_normalize_package_name(name: &str) -> String {
-    name.replace("-", "_")
+pub fn cargo_version() -> Result<String> {
+    let version = Command::new(&cargo_cmd())
+        .arg("--version")
+        .run()
+        .map_err(|_| {
+            Error::new(format!("{} is not installed", "cargo".bold()))
+                .with_note("you can install rust and cargo from https://rustup.rs")
+        })?;
+
+    Ok(version.trim().to_string())
 }
 
-fn cargo_output_path(
-    target: &Path,
-    profile: &str,
-    triple: &Triple,
-    package_name: &str,
-    crate_type: CargoCrateType,
-) -> Result<PathBuf> {
-    let package_name = cargo_normalize_package_name(package_name);
-    let filename = match (triple.operating_system, triple.environment, crate_type) {
-        (OperatingSystem::Linux, _, CargoCrateType::Cdylib) => {
-            format!("lib{}.so", package_name)
-        }
-        (OperatingSystem::Linux, _, CargoCrateType::Staticlib) => {
-            format!("lib{}.a", package_name)
-        }
-        (OperatingSystem::MacOSX(_), _, CargoCrateType::Cdylib)
-        | (OperatingSystem::Darwin(_), _, CargoCrateType::Cdylib) => {
-            format!("lib{}.dylib", package_name)
-        }
-        (OperatingSystem::MacOSX(_), _, CargoCrateType::Staticlib)
-        | (OperatingSystem::Darwin(_), _, CargoCrateType::Staticlib) => {
-            format!("lib{}.a", package_name)
-        }
-        (OperatingSystem::Windows, _, CargoCrateType::Cdylib) => {
-            format!("{}.dll", package_name)
-        }
-        (OperatingSystem::Windows, Environment::Msvc, CargoCrateType::Staticlib) => {
-            format!("{}.lib", package_name)
-        }
-        (OperatingSystem::Windows, _, CargoCrateType::Staticlib) => {
-            format!("lib{}.a", package_name)
-        }
-        _ => {
-            return Err(Error::new(format!(
-                "unsupported target: {} ",
-                triple.operating_system,
-            )));
-        }
-    };
+/// The targets rustup has a standard library installed for.
+pub fn installed_rust_targets() -> Result<Vec<String>> {
+    let targets = Command::new("rustup")
+        .arg("target")
+        .arg("list")
+        .arg("--installed")
+        .run()
+        .map_err(|_| {
+            Error::new(format!(
+                "{} is required to check the installed targets",
+                "rustup".bold()
+            ))
+            .with_note("you can install rustup from https://rustup.rs")
+        })?;
 
-    let profile_dir = match profile {
-        "release" | "bench" => "release",
-        "dev" | "test" => "debug",
-        x => x,
+    Ok(targets.lines().map(|x| x.trim().to_string()).collect())
+}
+
+/// Checks that rustup has the standard library for every triple, `cargo build` would only fail
+/// with a compile error otherwise. Without rustup cargo has to report it on its own.
+pub fn ensure_rust_targets(triples: &[Triple], install: bool) -> Result<()> {
+    let Ok(installed) = installed_rust_targets() else {
+        return Ok(());
     };
 
-    Ok(target
-        .join(triple.to_string())
-        .join(profile_dir)
-        .join(filename))
+    let mut missing = triples
+        .iter()
+        .map(|x| x.to_string())
+        .filter(|x| !installed.contains(x))
+        .collect::<Vec<_>>();
+    missing.sort();
+    missing.dedup();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    if install {
+        report_span!("installing rust targets {}", missing.join(", ").bold());
+        Command::new("rustup")
+            .arg("target")
+            .arg("add")
+            .args(&missing)
+            .run()?;
+        return Ok(());
+    }
+
+    Err(Error::new(format!(
+        "the rust standard library is not installed for {}",
+        missing.join(", ").bold()
+    ))
+    .with_note(format!(
+        "run `rustup target add {}` or build with {}",
+        missing.join(" "),
+        "--install-targets".bold()
+    )))
+}
+
+fn cargo_cmd() -> String {
+    var("CARGO").unwrap_or_else(|_| "cargo".to_string())
+}
+
+/// Extracts the package name from a package id, which is either in the
+/// `name version (source)` or the `source#name@version` format.
+fn cargo_package_id_name(id: &str) -> Option<String> {
+    match id.split_once('#') {
+        Some((_, fragment)) if fragment.contains('@') => {
+            fragment.split('@').next().map(|x| x.to_string())
+        }
+        Some((source, _)) => source
+            .split(['?', '#'])
+            .next()
+            .and_then(|x| x.trim_end_matches('/').rsplit('/').next())
+            .map(|x| x.to_string()),
+        None => id.split_whitespace().next().map(|x| x.to_string()),
+    }
 }
 
 #[derive(Debug)]
@@ -272,11 +465,17 @@ enum CargoMessage {
         libs: String,
     },
     CompilerMessage {
-        #[allow(dead_code)]
         message: String,
-        #[allow(dead_code)]
         package: String,
         rendered: String,
+        /// A warning about the code, not the "n warnings emitted" summary
+        warning: bool,
+    },
+    CompilerArtifact {
+        package: String,
+        crate_types: Vec<String>,
+        filenames: Vec<String>,
+        profile: Option<CargoProfile>,
     },
 }
 
@@ -308,11 +507,6 @@ impl FromStr for CargoMessage {
                 _ => return Err(()),
             };
 
-            let mut info_target = match value.remove("target") {
-                Some(JsonValue::Object(x)) => x,
-                _ => return Err(()),
-            };
-
             let rendered = match info_message.remove("rendered") {
                 Some(JsonValue::String(x)) => x,
                 _ => return Err(()),
@@ -323,8 +517,8 @@ impl FromStr for CargoMessage {
                 _ => return Err(()),
             };
 
-            let package = match info_target.remove("name") {
-                Some(JsonValue::String(x)) => x,
+            let package = match value.remove("package_id") {
+                Some(JsonValue::String(x)) => cargo_package_id_name(&x).ok_or(())?,
                 _ => return Err(()),
             };
 
@@ -335,10 +529,56 @@ impl FromStr for CargoMessage {
                 });
             }
 
+            let level = info_message.get("level").and_then(|x| x.get::<String>());
+            let has_spans = info_message
+                .get("spans")
+                .and_then(|x| x.get::<Vec<JsonValue>>())
+                .is_some_and(|x| !x.is_empty());
+
             Ok(CargoMessage::CompilerMessage {
                 rendered,
                 message,
                 package,
+                warning: level.is_some_and(|x| x == "warning") && has_spans,
+            })
+        } else if reason == "compiler-artifact" {
+            let package = match value.remove("package_id") {
+                Some(JsonValue::String(x)) => cargo_package_id_name(&x).ok_or(())?,
+                _ => return Err(()),
+            };
+
+            let crate_types = match value.remove("target") {
+                Some(JsonValue::Object(mut x)) => match x.remove("crate_types") {
+                    Some(JsonValue::Array(x)) => x
+                        .into_iter()
+                        .filter_map(|x| match x {
+                            JsonValue::String(x) => Some(x),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => return Err(()),
+                },
+                _ => return Err(()),
+            };
+
+            let filenames = match value.remove("filenames") {
+                Some(JsonValue::Array(x)) => x
+                    .into_iter()
+                    .filter_map(|x| match x {
+                        JsonValue::String(x) => Some(x),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => return Err(()),
+            };
+
+            let profile = value.get("profile").and_then(CargoProfile::from_json);
+
+            Ok(CargoMessage::CompilerArtifact {
+                package,
+                crate_types,
+                filenames,
+                profile,
             })
         } else if reason == "build-script-executed" {
             let linked_libs = match value.remove("linked_libs") {
@@ -374,3 +614,65 @@ impl FromStr for CargoMessage {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_id_name() {
+        let name = |id| cargo_package_id_name(id).unwrap_or_default();
+
+        assert_eq!(name("path+file:///work/example-clap#0.1.0"), "example-clap");
+        assert_eq!(
+            name("path+file:///work/plugins/gain#my-gain@0.2.0"),
+            "my-gain"
+        );
+        assert_eq!(
+            name("example-clap 0.1.0 (path+file:///work)"),
+            "example-clap"
+        );
+    }
+
+    #[test]
+    fn test_resolve_profile() {
+        let build_type = |profile: &str| {
+            let message = format!(
+                r#"{{"reason": "compiler-artifact", "package_id": "path+file:///work/gain#0.1.0", "target": {{"crate_types": ["staticlib"]}}, "profile": {}, "filenames": ["/work/target/release/libgain.a"]}}"#,
+                profile
+            );
+
+            match message.parse::<CargoMessage>() {
+                Ok(CargoMessage::CompilerArtifact { profile, .. }) => {
+                    profile.map(|x| x.cmake_build_type())
+                }
+                _ => None,
+            }
+        };
+
+        let profile = |opt_level: &str, debuginfo: &str| {
+            build_type(&format!(
+                r#"{{"opt_level": "{}", "debuginfo": {}, "debug_assertions": false}}"#,
+                opt_level, debuginfo
+            ))
+        };
+
+        assert_eq!(profile("0", "2"), Some(CmakeBuildType::Debug));
+        assert_eq!(profile("0", "0"), Some(CmakeBuildType::Debug));
+        assert_eq!(profile("3", "0"), Some(CmakeBuildType::Release));
+        assert_eq!(profile("3", "null"), Some(CmakeBuildType::Release));
+        assert_eq!(profile("3", "1"), Some(CmakeBuildType::RelWithDebInfo));
+        assert_eq!(profile("1", "2"), Some(CmakeBuildType::RelWithDebInfo));
+        assert_eq!(
+            profile("3", r#""line-tables-only""#),
+            Some(CmakeBuildType::RelWithDebInfo)
+        );
+        assert_eq!(
+            profile("3", r#""line-directives-only""#),
+            Some(CmakeBuildType::Release)
+        );
+        assert_eq!(profile("s", "0"), Some(CmakeBuildType::MinSizeRel));
+        assert_eq!(profile("z", "0"), Some(CmakeBuildType::MinSizeRel));
+        assert_eq!(build_type("null"), None);
+    }
+}
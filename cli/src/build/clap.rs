@@ -0,0 +1,752 @@
+#![allow(unsafe_code)]
+
+use crate::cli::{Command, Error, Result, report_message, report_span};
+use clap_sys::{
+    audio_buffer::clap_audio_buffer,
+    entry::clap_plugin_entry,
+    events::{
+        CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_MIDI, CLAP_EVENT_NOTE_OFF, CLAP_EVENT_NOTE_ON,
+        clap_event_header, clap_event_midi, clap_event_note, clap_input_events, clap_output_events,
+    },
+    ext::{
+        audio_ports::{
+            CLAP_AUDIO_PORT_IS_MAIN, CLAP_EXT_AUDIO_PORTS, clap_audio_port_info,
+            clap_plugin_audio_ports,
+        },
+        note_ports::{
+            CLAP_EXT_NOTE_PORTS, CLAP_NOTE_DIALECT_CLAP, CLAP_NOTE_DIALECT_MIDI,
+            CLAP_NOTE_DIALECT_MIDI_MPE, CLAP_NOTE_DIALECT_MIDI2, clap_note_port_info,
+            clap_plugin_note_ports,
+        },
+        state::{CLAP_EXT_STATE, clap_plugin_state},
+    },
+    factory::plugin_factory::{CLAP_PLUGIN_FACTORY_ID, clap_plugin_factory},
+    host::clap_host,
+    plugin::{clap_plugin, clap_plugin_descriptor},
+    process::{CLAP_PROCESS_ERROR, clap_process},
+    stream::{clap_istream, clap_ostream},
+    version::{CLAP_VERSION, clap_version_is_compatible},
+};
+use libloading::{Library, Symbol};
+use owo_colors::OwoColorize;
+use std::{
+    ffi::{CStr, CString, c_char, c_void},
+    path::{Path, PathBuf},
+    ptr::null,
+};
+
+#[derive(Debug, Clone)]
+pub struct ClapPluginInfo {
+    pub id: String,
+    pub name: String,
+    pub vendor: String,
+    pub version: String,
+    pub description: String,
+    pub features: Vec<String>,
+
+    pub audio_inputs: Vec<ClapAudioPort>,
+    pub audio_outputs: Vec<ClapAudioPort>,
+    pub note_inputs: Vec<ClapNotePort>,
+    pub note_outputs: Vec<ClapNotePort>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClapAudioPort {
+    pub id: u32,
+    pub name: String,
+    pub channel_count: u32,
+    pub port_type: Option<String>,
+    pub is_main: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClapNotePort {
+    pub id: u32,
+    pub name: String,
+    pub dialects: Vec<&'static str>,
+}
+
+/// Loads a `.clap` built for the host and reads the descriptors and port layouts
+/// of every plugin exposed by its plugin factory.
+pub fn inspect_clap(bundle: &Path) -> Result<Vec<ClapPluginInfo>> {
+    let library = ClapLibrary::load(bundle)?;
+    let mut output = Vec::new();
+
+    for descriptor in library.descriptors() {
+        let mut info = ClapPluginInfo {
+            id: read_str(descriptor.id),
+            name: read_str(descriptor.name),
+            vendor: read_str(descriptor.vendor),
+            version: read_str(descriptor.version),
+            description: read_str(descriptor.description),
+            features: read_str_array(descriptor.features),
+            audio_inputs: vec![],
+            audio_outputs: vec![],
+            note_inputs: vec![],
+            note_outputs: vec![],
+        };
+
+        let instance = library.create_plugin(&info.id)?;
+        for is_input in [true, false] {
+            let audio = instance.audio_ports(is_input);
+            let note = instance.note_ports(is_input);
+
+            if is_input {
+                info.audio_inputs = audio;
+                info.note_inputs = note;
+            } else {
+                info.audio_outputs = audio;
+                info.note_outputs = note;
+            }
+        }
+
+        output.push(info);
+    }
+
+    Ok(output)
+}
+
+/// Prefixes the stderr lines of `validate-clap` that describe why validation failed, anything
+/// else on stderr is output of the plugin itself.
+pub const CLAP_VALIDATION_FAILURE: &str = "validate-clap failure: ";
+
+/// Validates a `.clap` in a child process, so a crashing plugin can't take the bundler down.
+pub fn validate_clap_isolated(bundle: &Path) -> Result<()> {
+    report_span!("validating {}", bundle.display().bold());
+
+    let mut failures = Vec::new();
+    Command::new(&std::env::current_exe()?.to_string_lossy())
+        .arg("validate-clap")
+        .arg(bundle)
+        .run_stdout_stderr(
+            |line| {
+                report_message!("{}", line.trim());
+            },
+            |line| match line.strip_prefix(CLAP_VALIDATION_FAILURE) {
+                Some(failure) => failures.push(failure.trim().to_string()),
+                None if !line.trim().is_empty() => {
+                    report_message!("{}", line.trim());
+                }
+                None => {}
+            },
+        )
+        .map_err(|e| {
+            failures.into_iter().fold(
+                e.with_message(format!("{} failed validation", bundle.display().bold())),
+                |e, failure| e.with_note(failure),
+            )
+        })
+}
+
+/// Runs every plugin of a `.clap` through its lifecycle in this process: activation, processing
+/// silence and notes, saving and restoring the state and destruction.
+pub fn validate_clap(bundle: &Path) -> Result<()> {
+    const SAMPLE_RATE: f64 = 48000.0;
+    const BLOCK_SIZE: u32 = 512;
+    const BLOCK_COUNT: u32 = 16;
+
+    let library = ClapLibrary::load(bundle)?;
+    let ids = library
+        .descriptors()
+        .into_iter()
+        .map(|x| read_str(x.id))
+        .collect::<Vec<_>>();
+
+    if ids.is_empty() {
+        return Err(Error::new("the plugin factory does not expose any plugins"));
+    }
+
+    for id in ids {
+        let map_error = |e: Error| e.with_note(format!("while validating {}", id));
+
+        println!("instantiating {}", id);
+        let instance = library.create_plugin(&id).map_err(map_error)?;
+
+        println!("processing {} blocks of silence and notes", BLOCK_COUNT);
+        instance
+            .run_process(SAMPLE_RATE, BLOCK_SIZE, BLOCK_COUNT)
+            .map_err(map_error)?;
+
+        println!("saving and loading the state");
+        if let Some(state) = instance.save_state().map_err(map_error)? {
+            drop(instance);
+
+            let instance = library.create_plugin(&id).map_err(map_error)?;
+            instance.load_state(&state).map_err(map_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves the binary inside of a `.clap`, which is a plain shared library everywhere except
+/// macOS, where it is a bundle.
+pub fn clap_binary_path(bundle: &Path) -> PathBuf {
+    if bundle.is_dir() {
+        bundle
+            .join("Contents")
+            .join("MacOS")
+            .join(bundle.file_stem().unwrap_or_default())
+    } else {
+        bundle.to_path_buf()
+    }
+}
+
+pub struct ClapLibrary {
+    entry: *const clap_plugin_entry,
+    factory: *const clap_plugin_factory,
+    host: Box<clap_host>,
+    _library: Library,
+}
+
+impl ClapLibrary {
+    pub fn load(bundle: &Path) -> Result<Self> {
+        let binary = clap_binary_path(bundle);
+        let map_error = |e: Error| {
+            e.with_note(format!(
+                "make sure {} is a {} built for this machine",
+                bundle.display().bold(),
+                "clap".bold().bright_yellow()
+            ))
+        };
+
+        let library = unsafe { Library::new(&binary) }
+            .map_err(|e| map_error(Error::new(format!("failed to load plugin: {}", e))))?;
+
+        let entry = unsafe { library.get::<*const clap_plugin_entry>(b"clap_entry\0") }
+            .map(|x: Symbol<*const clap_plugin_entry>| *x)
+            .map_err(|_| {
+                map_error(Error::new(format!(
+                    "{} is not exported",
+                    "clap_entry".bold()
+                )))
+            })?;
+
+        let entry_ref = unsafe { &*entry };
+        if !clap_version_is_compatible(entry_ref.clap_version) {
+            return Err(Error::new(format!(
+                "incompatible clap version {}.{}.{}",
+                entry_ref.clap_version.major,
+                entry_ref.clap_version.minor,
+                entry_ref.clap_version.revision
+            )));
+        }
+
+        let path = CString::new(bundle.to_string_lossy().as_bytes())?;
+        let init = entry_ref
+            .init
+            .ok_or_else(|| Error::new("clap_entry.init is null"))?;
+
+        if !unsafe { init(path.as_ptr()) } {
+            return Err(Error::new("clap_entry.init returned false"));
+        }
+
+        let factory = entry_ref
+            .get_factory
+            .map(|x| unsafe { x(CLAP_PLUGIN_FACTORY_ID.as_ptr()) })
+            .unwrap_or(null()) as *const clap_plugin_factory;
+
+        let library = Self {
+            entry,
+            factory,
+            host: Box::new(clap_host {
+                clap_version: CLAP_VERSION,
+                host_data: std::ptr::null_mut(),
+                name: c"picobundler".as_ptr(),
+                vendor: c"picobundler".as_ptr(),
+                url: c"https://github.com/blepfx/picobundler".as_ptr(),
+                version: c"0.1.0".as_ptr(),
+                get_extension: Some(host_get_extension),
+                request_restart: Some(host_request),
+                request_process: Some(host_request),
+                request_callback: Some(host_request),
+            }),
+            _library: library,
+        };
+
+        if library.factory.is_null() {
+            return Err(Error::new(format!(
+                "{} does not provide a plugin factory",
+                bundle.display().bold()
+            )));
+        }
+
+        Ok(library)
+    }
+
+    pub fn descriptors(&self) -> Vec<&clap_plugin_descriptor> {
+        let factory = unsafe { &*self.factory };
+        let count = factory
+            .get_plugin_count
+            .map(|x| unsafe { x(self.factory) })
+            .unwrap_or(0);
+
+        (0..count)
+            .filter_map(|index| {
+                let descriptor = factory
+                    .get_plugin_descriptor
+                    .map(|x| unsafe { x(self.factory, index) })
+                    .unwrap_or(null());
+
+                unsafe { descriptor.as_ref() }
+            })
+            .collect()
+    }
+
+    pub fn create_plugin(&self, id: &str) -> Result<ClapInstance<'_>> {
+        let factory = unsafe { &*self.factory };
+        let plugin_id = CString::new(id)?;
+
+        let plugin = factory
+            .create_plugin
+            .map(|x| unsafe { x(self.factory, &*self.host, plugin_id.as_ptr()) })
+            .unwrap_or(null());
+
+        if plugin.is_null() {
+            return Err(Error::new(format!("failed to create plugin {}", id.bold())));
+        }
+
+        let instance = ClapInstance {
+            plugin,
+            _library: self,
+        };
+
+        let init = instance.vtable().init;
+        if !init.is_some_and(|x| unsafe { x(plugin) }) {
+            return Err(Error::new(format!(
+                "failed to initialize plugin {}",
+                id.bold()
+            )));
+        }
+
+        Ok(instance)
+    }
+}
+
+impl Drop for ClapLibrary {
+    fn drop(&mut self) {
+        if let Some(deinit) = unsafe { &*self.entry }.deinit {
+            unsafe { deinit() };
+        }
+    }
+}
+
+pub struct ClapInstance<'a> {
+    plugin: *const clap_plugin,
+    _library: &'a ClapLibrary,
+}
+
+impl ClapInstance<'_> {
+    fn vtable(&self) -> &clap_plugin {
+        unsafe { &*self.plugin }
+    }
+
+    fn extension<T>(&self, id: &CStr) -> Option<&T> {
+        let extension = self
+            .vtable()
+            .get_extension
+            .map(|x| unsafe { x(self.plugin, id.as_ptr()) })
+            .unwrap_or(null());
+
+        unsafe { (extension as *const T).as_ref() }
+    }
+
+    pub fn audio_ports(&self, is_input: bool) -> Vec<ClapAudioPort> {
+        let Some(ext) = self.extension::<clap_plugin_audio_ports>(CLAP_EXT_AUDIO_PORTS) else {
+            return vec![];
+        };
+
+        let (Some(count), Some(get)) = (ext.count, ext.get) else {
+            return vec![];
+        };
+
+        (0..unsafe { count(self.plugin, is_input) })
+            .filter_map(|index| {
+                let mut info = unsafe { std::mem::zeroed::<clap_audio_port_info>() };
+                if !unsafe { get(self.plugin, index, is_input, &mut info) } {
+                    return None;
+                }
+
+                Some(ClapAudioPort {
+                    id: info.id,
+                    name: read_name(&info.name),
+                    channel_count: info.channel_count,
+                    port_type: (!info.port_type.is_null()).then(|| read_str(info.port_type)),
+                    is_main: info.flags & CLAP_AUDIO_PORT_IS_MAIN != 0,
+                })
+            })
+            .collect()
+    }
+
+    /// Activates the plugin and processes `block_count` blocks of silence, with a note played
+    /// on every note input port.
+    pub fn run_process(&self, sample_rate: f64, block_size: u32, block_count: u32) -> Result<()> {
+        let inputs = self.audio_ports(true);
+        let outputs = self.audio_ports(false);
+        let notes = self.note_ports(true);
+
+        let activate = self.vtable().activate;
+        if !activate.is_some_and(|x| unsafe { x(self.plugin, sample_rate, 1, block_size) }) {
+            return Err(Error::new("clap_plugin.activate returned false"));
+        }
+
+        // the plugin has to be stopped before it's deactivated, however processing ended
+        let start_processing = self.vtable().start_processing;
+        let started = start_processing.is_some_and(|x| unsafe { x(self.plugin) });
+
+        let result = (|| {
+            if !started {
+                return Err(Error::new("clap_plugin.start_processing returned false"));
+            }
+
+            let mut input_buffers = AudioBuffers::new(&inputs, block_size);
+            let mut output_buffers = AudioBuffers::new(&outputs, block_size);
+
+            for block in 0..block_count {
+                let mut events = InputEvents::default();
+                for (index, port) in notes.iter().enumerate() {
+                    match block {
+                        1 => events.push_note(port, index as u16, true),
+                        3 => events.push_note(port, index as u16, false),
+                        _ => {}
+                    }
+                }
+
+                input_buffers.clear();
+                output_buffers.clear();
+
+                let in_events = events.as_raw();
+                let process = clap_process {
+                    steady_time: (block * block_size) as i64,
+                    frames_count: block_size,
+                    transport: null(),
+                    audio_inputs: input_buffers.raw.as_ptr(),
+                    audio_outputs: output_buffers.raw.as_mut_ptr(),
+                    audio_inputs_count: input_buffers.raw.len() as u32,
+                    audio_outputs_count: output_buffers.raw.len() as u32,
+                    in_events: &in_events,
+                    out_events: &OUTPUT_EVENTS,
+                };
+
+                let status = self
+                    .vtable()
+                    .process
+                    .map(|x| unsafe { x(self.plugin, &process) })
+                    .unwrap_or(CLAP_PROCESS_ERROR);
+
+                if status == CLAP_PROCESS_ERROR {
+                    return Err(Error::new(format!(
+                        "clap_plugin.process returned an error on block {}",
+                        block
+                    )));
+                }
+
+                if !output_buffers.is_finite() {
+                    return Err(Error::new(format!(
+                        "clap_plugin.process produced NaN or infinite samples on block {}",
+                        block
+                    )));
+                }
+            }
+
+            Ok(())
+        })();
+
+        if let (true, Some(stop_processing)) = (started, self.vtable().stop_processing) {
+            unsafe { stop_processing(self.plugin) };
+        }
+
+        if let Some(deactivate) = self.vtable().deactivate {
+            unsafe { deactivate(self.plugin) };
+        }
+
+        result
+    }
+
+    /// Returns `None` if the plugin doesn't implement the state extension.
+    pub fn save_state(&self) -> Result<Option<Vec<u8>>> {
+        let Some(save) = self
+            .extension::<clap_plugin_state>(CLAP_EXT_STATE)
+            .and_then(|x| x.save)
+        else {
+            return Ok(None);
+        };
+
+        unsafe extern "C" fn write(
+            stream: *const clap_ostream,
+            buffer: *const c_void,
+            size: u64,
+        ) -> i64 {
+            let output = unsafe { &mut *((*stream).ctx as *mut Vec<u8>) };
+            let buffer = unsafe { std::slice::from_raw_parts(buffer as *const u8, size as usize) };
+            output.extend_from_slice(buffer);
+            size as i64
+        }
+
+        let mut state = Vec::new();
+        let stream = clap_ostream {
+            ctx: &mut state as *mut Vec<u8> as *mut c_void,
+            write: Some(write),
+        };
+
+        if !unsafe { save(self.plugin, &stream) } {
+            return Err(Error::new("clap_plugin_state.save returned false"));
+        }
+
+        Ok(Some(state))
+    }
+
+    pub fn load_state(&self, state: &[u8]) -> Result<()> {
+        let Some(load) = self
+            .extension::<clap_plugin_state>(CLAP_EXT_STATE)
+            .and_then(|x| x.load)
+        else {
+            return Err(Error::new("clap_plugin_state.load is missing"));
+        };
+
+        unsafe extern "C" fn read(
+            stream: *const clap_istream,
+            buffer: *mut c_void,
+            size: u64,
+        ) -> i64 {
+            let input = unsafe { &mut *((*stream).ctx as *mut &[u8]) };
+            let count = input.len().min(size as usize);
+            unsafe { std::ptr::copy_nonoverlapping(input.as_ptr(), buffer as *mut u8, count) };
+            *input = &input[count..];
+            count as i64
+        }
+
+        let mut input = state;
+        let stream = clap_istream {
+            ctx: &mut input as *mut &[u8] as *mut c_void,
+            read: Some(read),
+        };
+
+        if !unsafe { load(self.plugin, &stream) } {
+            return Err(Error::new(
+                "clap_plugin_state.load returned false for a state saved by the same plugin",
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn note_ports(&self, is_input: bool) -> Vec<ClapNotePort> {
+        let Some(ext) = self.extension::<clap_plugin_note_ports>(CLAP_EXT_NOTE_PORTS) else {
+            return vec![];
+        };
+
+        let (Some(count), Some(get)) = (ext.count, ext.get) else {
+            return vec![];
+        };
+
+        (0..unsafe { count(self.plugin, is_input) })
+            .filter_map(|index| {
+                let mut info = unsafe { std::mem::zeroed::<clap_note_port_info>() };
+                if !unsafe { get(self.plugin, index, is_input, &mut info) } {
+                    return None;
+                }
+
+                let dialects = [
+                    (CLAP_NOTE_DIALECT_CLAP, "clap"),
+                    (CLAP_NOTE_DIALECT_MIDI, "midi"),
+                    (CLAP_NOTE_DIALECT_MIDI_MPE, "midi-mpe"),
+                    (CLAP_NOTE_DIALECT_MIDI2, "midi2"),
+                ];
+
+                Some(ClapNotePort {
+                    id: info.id,
+                    name: read_name(&info.name),
+                    dialects: dialects
+                        .into_iter()
+                        .filter(|(flag, _)| info.supported_dialects & flag != 0)
+                        .map(|(_, name)| name)
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Drop for ClapInstance<'_> {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.vtable().destroy {
+            unsafe { destroy(self.plugin) };
+        }
+    }
+}
+
+struct AudioBuffers {
+    channels: Vec<Vec<f32>>,
+    pointers: Vec<*mut f32>,
+    raw: Vec<clap_audio_buffer>,
+}
+
+impl AudioBuffers {
+    fn new(ports: &[ClapAudioPort], block_size: u32) -> Self {
+        let channel_count = ports.iter().map(|x| x.channel_count as usize).sum();
+        let mut channels = vec![vec![0.0f32; block_size as usize]; channel_count];
+        let mut pointers = channels
+            .iter_mut()
+            .map(|x| x.as_mut_ptr())
+            .collect::<Vec<_>>();
+
+        let mut offset = 0;
+        let mut raw = Vec::new();
+        for port in ports {
+            raw.push(clap_audio_buffer {
+                data32: unsafe { pointers.as_mut_ptr().add(offset) },
+                data64: std::ptr::null_mut(),
+                channel_count: port.channel_count,
+                latency: 0,
+                constant_mask: 0,
+            });
+
+            offset += port.channel_count as usize;
+        }
+
+        Self {
+            channels,
+            pointers,
+            raw,
+        }
+    }
+
+    fn clear(&mut self) {
+        for (channel, pointer) in self.channels.iter_mut().zip(&self.pointers) {
+            // the plugin is not allowed to swap out the channel pointers
+            debug_assert_eq!(channel.as_mut_ptr(), *pointer);
+            channel.fill(0.0);
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        self.channels.iter().flatten().all(|x| x.is_finite())
+    }
+}
+
+enum InputEvent {
+    Note(clap_event_note),
+    Midi(clap_event_midi),
+}
+
+#[derive(Default)]
+struct InputEvents(Vec<InputEvent>);
+
+impl InputEvents {
+    fn push_note(&mut self, port: &ClapNotePort, port_index: u16, on: bool) {
+        const KEY: u8 = 60;
+
+        if port.dialects.contains(&"midi") {
+            self.0.push(InputEvent::Midi(clap_event_midi {
+                header: clap_event_header {
+                    size: size_of::<clap_event_midi>() as u32,
+                    time: 0,
+                    space_id: CLAP_CORE_EVENT_SPACE_ID,
+                    type_: CLAP_EVENT_MIDI,
+                    flags: 0,
+                },
+                port_index,
+                data: if on { [0x90, KEY, 100] } else { [0x80, KEY, 0] },
+            }));
+        } else if port.dialects.contains(&"clap") {
+            self.0.push(InputEvent::Note(clap_event_note {
+                header: clap_event_header {
+                    size: size_of::<clap_event_note>() as u32,
+                    time: 0,
+                    space_id: CLAP_CORE_EVENT_SPACE_ID,
+                    type_: if on {
+                        CLAP_EVENT_NOTE_ON
+                    } else {
+                        CLAP_EVENT_NOTE_OFF
+                    },
+                    flags: 0,
+                },
+                note_id: -1,
+                port_index: port_index as i16,
+                channel: 0,
+                key: KEY as i16,
+                velocity: if on { 0.8 } else { 0.0 },
+            }));
+        }
+    }
+
+    fn as_raw(&self) -> clap_input_events {
+        unsafe extern "C" fn size(list: *const clap_input_events) -> u32 {
+            unsafe { &*((*list).ctx as *const InputEvents) }.0.len() as u32
+        }
+
+        unsafe extern "C" fn get(
+            list: *const clap_input_events,
+            index: u32,
+        ) -> *const clap_event_header {
+            let events = unsafe { &*((*list).ctx as *const InputEvents) };
+            match events.0.get(index as usize) {
+                Some(InputEvent::Note(x)) => &x.header,
+                Some(InputEvent::Midi(x)) => &x.header,
+                None => null(),
+            }
+        }
+
+        clap_input_events {
+            ctx: self as *const Self as *mut c_void,
+            size: Some(size),
+            get: Some(get),
+        }
+    }
+}
+
+static OUTPUT_EVENTS: clap_output_events = clap_output_events {
+    ctx: std::ptr::null_mut(),
+    try_push: Some(output_events_try_push),
+};
+
+unsafe extern "C" fn output_events_try_push(
+    _: *const clap_output_events,
+    _: *const clap_event_header,
+) -> bool {
+    true
+}
+
+unsafe extern "C" fn host_get_extension(_: *const clap_host, _: *const c_char) -> *const c_void {
+    null()
+}
+
+unsafe extern "C" fn host_request(_: *const clap_host) {}
+
+fn read_str(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Reads a fixed size name array, which a plugin may fill up without a terminating nul.
+fn read_name(name: &[c_char]) -> String {
+    let bytes = name
+        .iter()
+        .map(|x| u8::from_ne_bytes(x.to_ne_bytes()))
+        .collect::<Vec<_>>();
+
+    match CStr::from_bytes_until_nul(&bytes) {
+        Ok(name) => name.to_string_lossy().into_owned(),
+        Err(_) => String::from_utf8_lossy(&bytes).into_owned(),
+    }
+}
+
+fn read_str_array(mut ptr: *const *const c_char) -> Vec<String> {
+    let mut output = Vec::new();
+    if ptr.is_null() {
+        return output;
+    }
+
+    while let Some(item) = unsafe { ptr.as_ref() }.filter(|x| !x.is_null()) {
+        output.push(read_str(*item));
+        ptr = unsafe { ptr.add(1) };
+    }
+
+    output
+}
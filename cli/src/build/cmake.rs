@@ -3,13 +3,58 @@ use crate::{
     report_message,
 };
 use owo_colors::OwoColorize;
-use std::path::PathBuf;
+use std::{
+    fmt::Display,
+    hash::Hasher,
+    io::Read,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CmakeBuildType {
+    Debug,
+    Release,
+    RelWithDebInfo,
+    MinSizeRel,
+}
+
+impl Display for CmakeBuildType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Debug => write!(f, "Debug"),
+            Self::Release => write!(f, "Release"),
+            Self::RelWithDebInfo => write!(f, "RelWithDebInfo"),
+            Self::MinSizeRel => write!(f, "MinSizeRel"),
+        }
+    }
+}
+
+impl FromStr for CmakeBuildType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(Self::Debug),
+            "release" => Ok(Self::Release),
+            "relwithdebinfo" => Ok(Self::RelWithDebInfo),
+            "minsizerel" => Ok(Self::MinSizeRel),
+            _ => Err(Error::new(format!("unknown cmake build type {}", s.bold()))
+                .with_note("use one of Debug, Release, RelWithDebInfo or MinSizeRel")),
+        }
+    }
+}
 
 pub struct ClapWrapperOptions {
     pub cmake_dir: PathBuf,
+    /// Must not be shared with any other package, target, profile or set of formats
     pub build_dir: PathBuf,
+    pub build_type: CmakeBuildType,
 
     pub package_name: String,
+    pub output_name: String,
+    pub bundle_id: Option<String>,
+    pub vendor: Option<String>,
     pub static_lib: PathBuf,
 
     pub native_static_libs: Option<String>,
@@ -29,48 +74,111 @@ pub struct ClapWrapperOutput {
 pub fn build_wrapper(options: ClapWrapperOptions) -> Result<ClapWrapperOutput> {
     report_span!("wrapping via {}", "clap-wrapper".bold());
 
-    let build_dir = options.build_dir.join(match &options.zig_triple {
-        Some(triple) => triple.clone(),
-        None => "host".to_string(),
-    });
-
+    let build_dir = options.build_dir;
     let _ = std::fs::create_dir_all(&build_dir);
 
+    let output = build_dir
+        .join("clap-wrapper-output")
+        .join(options.package_name.clone())
+        .join(options.output_name.clone());
+
+    let output = ClapWrapperOutput {
+        clap: output.with_extension("clap"),
+        vst3: options.vst3.as_ref().map(|_| output.with_extension("vst3")),
+        auv2: if options.auv2 && options.osx_arch.is_some() {
+            Some(output.with_extension("component"))
+        } else {
+            None
+        },
+    };
+
+    let mut fingerprint = vec![
+        ("cmake dir", options.cmake_dir.display().to_string()),
+        ("cmake sources", hash_dir(&options.cmake_dir)?),
+        ("static library", hash_file(&options.static_lib)?),
+    ];
+
     #[rustfmt::skip]
     let envs = vec![ 
         ("PICO_PLUGIN_STATIC_LIB", options.static_lib.into_os_string()),
         ("PICO_PLUGIN_NAME", options.package_name.clone().into()),
+        ("PICO_PLUGIN_OUTPUT_NAME", options.output_name.clone().into()),
+        ("PICO_PLUGIN_BUNDLE_ID", options.bundle_id.clone().unwrap_or_default().into()),
+        ("PICO_PLUGIN_VENDOR", options.vendor.clone().unwrap_or_default().into()),
         ("PICO_PLUGIN_WANT_AUV2", if options.auv2 && options.osx_arch.is_some() { "AUV2" } else { "" }.into()),
         ("PICO_PLUGIN_WANT_VST3", options.vst3.is_some().then_some("VST3").unwrap_or_default().into()),
         ("PICO_SDK_VST3", options.vst3.clone().map(|v| v.into_os_string()).unwrap_or_default()),
         ("PICO_BUILD_ZIG_TARGET", options.zig_triple.map(|v| v.into()).unwrap_or_default()),
         ("PICO_BUILD_OSX_ARCH", options.osx_arch.clone().map(|v| v.into()).unwrap_or_default()),
-        ("PICO_BUILD_TYPE", "Release".into()), //TODO: use cargo profile to set this
+        ("PICO_BUILD_TYPE", options.build_type.to_string().into()),
         ("PICO_BUILD_NATIVE_STATIC_LIBS", options.native_static_libs.map(format_native_static_libs).unwrap_or_default().into()),
     ];
 
-    Command::new("cmake")
-        .arg(&options.cmake_dir)
-        .cwd(&build_dir)
-        .envs(envs.iter().map(|(k, v)| (k, v.as_os_str())))
-        .run_stdout_stderr(
-            |line| {
-                report_message!("{}", line);
-            },
-            |line| {
-                report_message!("{}", line);
-            },
-        )
-        .map_err(|e| {
-            e.with_message(format!(
-                "failed to configure {}. check the output in --verbose mode for more info",
-                "clap-wrapper".bold()
-            ))
-        })?;
+    fingerprint.extend(
+        envs.iter()
+            .map(|(k, v)| (*k, v.to_string_lossy().to_string())),
+    );
+
+    // inherited by cmake, these pick the compilers and the apple SDK
+    fingerprint.extend(
+        ["CC", "CXX", "SDKROOT", "MACOSX_DEPLOYMENT_TARGET"]
+            .into_iter()
+            .map(|k| (k, std::env::var(k).unwrap_or_default())),
+    );
+
+    let fingerprint_path = build_dir.join(FINGERPRINT_FILE);
+    let previous = read_fingerprint(&fingerprint_path);
+    let changes = fingerprint_changes(previous.as_deref(), &fingerprint);
+
+    let outputs_exist = output.clap.exists()
+        && output.vst3.as_ref().is_none_or(|x| x.exists())
+        && output.auv2.as_ref().is_none_or(|x| x.exists());
+
+    if changes.is_empty() && outputs_exist {
+        report_message!("inputs are unchanged, skipping {}", "clap-wrapper".bold());
+        return Ok(output);
+    }
+
+    for (_, change) in &changes {
+        report_message!("rebuilding {}: {}", "clap-wrapper".bold(), change);
+    }
+
+    if changes.is_empty() {
+        report_message!("rebuilding {}: outputs are missing", "clap-wrapper".bold());
+    }
+
+    // a failed build must not leave a matching fingerprint behind
+    let _ = std::fs::remove_file(&fingerprint_path);
+
+    // the static library is only read by the build step, it doesn't need a new configure
+    let needs_configure = changes.iter().any(|(key, _)| *key != "static library");
+
+    if needs_configure {
+        Command::new("cmake")
+            .arg(&options.cmake_dir)
+            .cwd(&build_dir)
+            .envs(envs.iter().map(|(k, v)| (k, v.as_os_str())))
+            .run_stdout_stderr(
+                |line| {
+                    report_message!("{}", line);
+                },
+                |line| {
+                    report_message!("{}", line);
+                },
+            )
+            .map_err(|e| {
+                e.with_message(format!(
+                    "failed to configure {}. check the output in --verbose mode for more info",
+                    "clap-wrapper".bold()
+                ))
+            })?;
+    }
 
     Command::new("cmake")
         .arg("--build")
         .arg(".")
+        .arg("--config")
+        .arg(options.build_type.to_string())
         .cwd(&build_dir)
         .envs(envs.iter().map(|(k, v)| (k, v.as_os_str())))
         .run_stdout_stderr(
@@ -88,35 +196,199 @@ pub fn build_wrapper(options: ClapWrapperOptions) -> Result<ClapWrapperOutput> {
             ))
         })?;
 
-    let output = build_dir
-        .join("clap-wrapper-output")
-        .join(options.package_name.clone())
-        .join(options.package_name.clone());
+    write_fingerprint(&fingerprint_path, &fingerprint)?;
 
-    Ok(ClapWrapperOutput {
-        clap: output.with_extension("clap"),
-        vst3: options.vst3.map(|_| output.with_extension("vst3")),
-        auv2: if options.auv2 && options.osx_arch.is_some() {
-            Some(output.with_extension("component"))
-        } else {
-            None
-        },
-    })
+    Ok(output)
+}
+
+/// Stored in the build directory, lists every input of the last successful wrapper build.
+const FINGERPRINT_FILE: &str = "picobundler-fingerprint";
+
+/// 64 bit FNV-1a. Fingerprints outlive the build of picobundler that wrote them, so unlike
+/// `DefaultHasher` the hash must never change.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut hasher = Fnv1a::default();
+    hash_contents(path, &mut hasher)?;
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+/// Hashes the names, sizes and modification times of every file below `dir`, like the wrapper
+/// sources and the zig scripts next to `CMakeLists.txt`. Reading the contents of the whole
+/// clap-wrapper tree would make every no-op rebuild slow. Links are hashed by their target
+/// instead of being followed, so a link loop can't make the walk endless.
+fn hash_dir(dir: &Path) -> Result<String> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        let entries = std::fs::read_dir(&current).map_err(|e| {
+            Error::new(format!("failed to read {}", current.display())).with_note(e)
+        })?;
+
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+
+            if file_type.is_symlink() {
+                let target = std::fs::read_link(&path)?;
+                files.push((path, format!("-> {}", target.display())));
+            } else if file_type.is_dir() {
+                dirs.push(path);
+            } else {
+                let metadata = entry.metadata()?;
+                let modified = metadata
+                    .modified()?
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                files.push((path, format!("{} {}", metadata.len(), modified.as_nanos())));
+            }
+        }
+    }
+
+    files.sort();
+
+    let mut hasher = Fnv1a::default();
+    for (file, stamp) in files {
+        let name = file.strip_prefix(dir).unwrap_or(&file).to_string_lossy();
+        hasher.write(name.as_bytes());
+        hasher.write(&[0]);
+        hasher.write(stamp.as_bytes());
+        hasher.write(&[0]);
+    }
+
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn hash_contents(path: &Path, hasher: &mut Fnv1a) -> Result<()> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| Error::new(format!("failed to read {}", path.display())).with_note(e))?;
+
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => hasher.write(&buffer[..n]),
+        }
+    }
+
+    Ok(())
+}
+
+fn read_fingerprint(path: &Path) -> Option<Vec<(String, String)>> {
+    let fingerprint = std::fs::read_to_string(path).ok()?;
+    Some(
+        fingerprint
+            .lines()
+            .filter_map(|x| x.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    )
+}
+
+fn write_fingerprint(path: &Path, fingerprint: &[(&str, String)]) -> Result<()> {
+    let fingerprint = fingerprint
+        .iter()
+        .map(|(k, v)| format!("{}={}\n", k, v))
+        .collect::<String>();
+
+    std::fs::write(path, fingerprint)?;
+    Ok(())
+}
+
+/// Returns every input that differs from the previous build, with a description for
+/// `--verbose`.
+fn fingerprint_changes<'a>(
+    previous: Option<&[(String, String)]>,
+    current: &[(&'a str, String)],
+) -> Vec<(&'a str, String)> {
+    let Some(previous) = previous else {
+        return vec![("", "no previous build".to_string())];
+    };
+
+    current
+        .iter()
+        .filter_map(
+            |(key, value)| match previous.iter().find(|(x, _)| x == key) {
+                Some((_, old)) if old == value => None,
+                Some((_, old)) => Some((
+                    *key,
+                    format!("{} changed from {:?} to {:?}", key, old, value),
+                )),
+                None => Some((*key, format!("{} is new", key))),
+            },
+        )
+        .collect()
 }
 
 pub fn ensure_cmake_installed() -> Result<()> {
-    Command::new("cmake").arg("--version").run().map_err(|_| {
+    cmake_version().map(|_| ())
+}
+
+/// The version of the installed cmake, fails if it's missing or older than what
+/// `CMakeLists.txt` requires.
+pub fn cmake_version() -> Result<String> {
+    const MIN_VERSION: (u32, u32) = (3, 27);
+
+    let error = || {
         Error::new(format!(
-            "vst3/auv2 bundling requires {} to be installed",
-            "cmake".bold()
+            "vst3/auv2 bundling requires {} (>= {}.{}) to be installed",
+            "cmake".bold(),
+            MIN_VERSION.0,
+            MIN_VERSION.1
         ))
         .with_note(format!(
             "you can install {} from https://cmake.org",
             "cmake".bold()
         ))
-    })?;
+    };
 
-    Ok(())
+    let output = Command::new("cmake")
+        .arg("--version")
+        .run()
+        .map_err(|_| error())?;
+
+    // "cmake version 3.28.1"
+    let version = output
+        .lines()
+        .next()
+        .and_then(|x| x.strip_prefix("cmake version "))
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+
+    let mut parts = version
+        .split('.')
+        .map(|x| x.parse::<u32>().unwrap_or_default());
+    let found = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+
+    if found < MIN_VERSION {
+        return Err(error().with_note(format!("found cmake {}", version)));
+    }
+
+    Ok(version)
 }
 
 fn format_native_static_libs(native_static_libs: String) -> String {
@@ -152,4 +424,38 @@ mod tests {
         let expected = "objc;-framework CoreFoundation;iconv;System;c;m";
         assert_eq!(format_native_static_libs(input.to_string()), expected);
     }
+
+    #[test]
+    fn test_fnv1a() {
+        let hash = |chunks: &[&[u8]]| {
+            let mut hasher = Fnv1a::default();
+            chunks.iter().for_each(|x| hasher.write(x));
+            hasher.finish()
+        };
+
+        assert_eq!(hash(&[]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(&[b"a"]), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(&[b"foobar"]), 0x8594_4171_f739_67e8);
+        assert_eq!(hash(&[b"foo", b"bar"]), hash(&[b"foobar"]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_dir_links() {
+        let dir = std::env::temp_dir().join(format!("picobundler-hash-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sdk")).unwrap();
+        std::fs::write(dir.join("sdk/CMakeLists.txt"), "project(sdk)").unwrap();
+
+        // a link back to the root would be walked forever if it was followed
+        let before = hash_dir(&dir).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("sdk/loop")).unwrap();
+        let linked = hash_dir(&dir).unwrap();
+        assert_ne!(before, linked);
+
+        std::fs::write(dir.join("sdk/CMakeLists.txt"), "project(sdk VERSION 2)").unwrap();
+        assert_ne!(hash_dir(&dir).unwrap(), linked);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,261 @@
+use super::{
+    BuildTarget, Vst3Sdk, cargo_version, cmake::cmake_version, find_program,
+    installed_rust_targets, zig::zig_version,
+};
+use crate::cli::{Command, Error, Result};
+use owo_colors::OwoColorize;
+use std::fmt::Display;
+use target_lexicon::OperatingSystem;
+
+/// What `doctor` checks the tools for, mirrors the formats and targets of a build.
+#[derive(Debug, Clone)]
+pub struct DoctorRequest {
+    pub targets: Vec<BuildTarget>,
+    pub auv2: bool,
+    pub vst3: Option<Vst3Sdk>,
+    pub sign: bool,
+}
+
+pub struct DoctorCheck {
+    /// The tool or target that was checked
+    pub name: String,
+    /// Why the build needs it
+    pub needed_for: String,
+    /// The version or path that was found, the error notes are the fix hints
+    pub result: Result<String>,
+}
+
+pub fn run_doctor(request: &DoctorRequest) -> Vec<DoctorCheck> {
+    let mut checks = Vec::new();
+    let mut check = |name: &str, needed_for: &str, result: Result<String>| {
+        checks.push(DoctorCheck {
+            name: name.to_string(),
+            needed_for: needed_for.to_string(),
+            result,
+        })
+    };
+
+    let use_zig = request.targets.iter().any(|x| x.needs_zig());
+    let use_cmake = request.auv2
+        || request.vst3.is_some()
+        || request.targets.iter().any(|x| x.needs_cmake_link());
+    let is_apple = |x: &BuildTarget| {
+        matches!(
+            x.operating_system(),
+            OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_)
+        )
+    };
+    let has_apple = request.targets.iter().any(is_apple);
+
+    check("cargo", "building the plugins", cargo_version());
+    check_rust_targets(&request.targets, &mut check);
+
+    if use_zig {
+        check("zig", "cross compilation", zig_version());
+    }
+
+    if use_cmake {
+        check("cmake", "the vst3/auv2 wrapper", cmake_version());
+    }
+
+    // zig brings its own compiler for the cross compiled targets
+    if use_cmake && request.targets.iter().any(|x| !x.needs_zig()) {
+        check("c++ compiler", "the vst3/auv2 wrapper", cxx_compiler());
+    }
+
+    if use_cmake {
+        check(
+            "git",
+            "downloading the wrapper sources",
+            program_version(
+                "git",
+                &["--version"],
+                "you can install git from https://git-scm.com",
+            ),
+        );
+    }
+
+    if matches!(request.vst3, Some(Vst3Sdk::Proprietary)) {
+        if cfg!(windows) {
+            check(
+                "powershell",
+                "downloading the vst3 sdk",
+                program(
+                    "powershell",
+                    "install PowerShell from https://aka.ms/powershell",
+                ),
+            );
+        } else {
+            check(
+                "curl",
+                "downloading the vst3 sdk",
+                program("curl", install_hint("curl")),
+            );
+            check(
+                "unzip",
+                "downloading the vst3 sdk",
+                program("unzip", install_hint("unzip")),
+            );
+        }
+    }
+
+    if request.targets.contains(&BuildTarget::AppleUniversal) {
+        check("lipo", "universal binaries", program("lipo", xcode_hint()));
+    }
+
+    if has_apple {
+        check(
+            "codesign",
+            "signing apple bundles",
+            program("codesign", xcode_hint()),
+        );
+    }
+
+    if has_apple && request.sign {
+        check(
+            "zip",
+            "uploading bundles for notarization",
+            program("zip", install_hint("zip")),
+        );
+        check(
+            "notarytool",
+            "notarizing apple bundles",
+            program_version("xcrun", &["notarytool", "--version"], xcode_hint())
+                .map(|x| format!("notarytool {}", x)),
+        );
+    }
+
+    checks
+}
+
+fn check_rust_targets(targets: &[BuildTarget], check: &mut impl FnMut(&str, &str, Result<String>)) {
+    let mut triples = targets
+        .iter()
+        .flat_map(|x| x.rust_triples())
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    triples.sort();
+    triples.dedup();
+
+    let installed = match installed_rust_targets() {
+        Ok(installed) => installed,
+        Err(e) => {
+            check("rustup", "checking the installed targets", Err(e));
+            return;
+        }
+    };
+
+    for triple in triples {
+        let result = match installed.contains(&triple) {
+            true => Ok("installed".to_string()),
+            false => Err(Error::new(format!(
+                "the rust target {} is not installed",
+                triple.bold()
+            ))
+            .with_note(format!(
+                "run `rustup target add {}` or build with {}",
+                triple,
+                "--install-targets".bold()
+            ))),
+        };
+
+        check(&triple, "the rust standard library", result);
+    }
+}
+
+fn cxx_compiler() -> Result<String> {
+    if let Ok(cxx) = std::env::var("CXX") {
+        if std::path::Path::new(&cxx).is_file() {
+            return Ok(cxx);
+        }
+
+        return program(
+            &cxx,
+            "the compiler set in the CXX environment variable doesn't exist",
+        );
+    }
+
+    let candidates: &[&str] = match target_lexicon::HOST.operating_system {
+        OperatingSystem::Windows => &["cl", "clang-cl"],
+        OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_) => &["clang++"],
+        _ => &["c++", "g++", "clang++"],
+    };
+
+    if let Some(path) = candidates.iter().find_map(|x| find_program(x)) {
+        return Ok(path.display().to_string());
+    }
+
+    // cmake finds Visual Studio on its own, cl.exe is only on PATH in a developer prompt
+    if cfg!(windows) {
+        let vswhere = std::env::var_os("ProgramFiles(x86)").map(|x| {
+            std::path::PathBuf::from(x)
+                .join("Microsoft Visual Studio")
+                .join("Installer")
+                .join("vswhere.exe")
+        });
+
+        if let Some(vswhere) = vswhere.filter(|x| x.is_file()) {
+            let path = Command::new(&vswhere.to_string_lossy())
+                .arg("-latest")
+                .arg("-requires")
+                .arg("Microsoft.VisualStudio.Component.VC.Tools.x86.x64")
+                .arg("-property")
+                .arg("installationPath")
+                .run()
+                .unwrap_or_default();
+
+            if !path.trim().is_empty() {
+                return Ok(format!("Visual Studio in {}", path.trim()));
+            }
+        }
+    }
+
+    let hint = match target_lexicon::HOST.operating_system {
+        OperatingSystem::Windows => {
+            "install Visual Studio with the \"Desktop development with C++\" workload".to_string()
+        }
+        OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_) => xcode_hint(),
+        _ => install_hint("g++"),
+    };
+
+    Err(Error::new("no C++ compiler found").with_note(hint))
+}
+
+/// Checks that `name` is on `PATH`, returns its path.
+fn program(name: &str, hint: impl Display) -> Result<String> {
+    match find_program(name) {
+        Some(path) => Ok(path.display().to_string()),
+        None => Err(Error::new(format!("{} was not found", name.bold())).with_note(hint)),
+    }
+}
+
+/// Runs `name` with `args` and returns the first line of its output.
+fn program_version(name: &str, args: &[&str], hint: impl Display) -> Result<String> {
+    let output = Command::new(name)
+        .args(args)
+        .run()
+        .map_err(|_| Error::new(format!("{} was not found", name.bold())).with_note(hint))?;
+
+    Ok(output.lines().next().unwrap_or_default().trim().to_string())
+}
+
+fn install_hint(package: &str) -> String {
+    match target_lexicon::HOST.operating_system {
+        OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_) => {
+            format!("install it with `brew install {}`", package)
+        }
+        _ => format!(
+            "install it with your package manager, e.g. `sudo apt install {}`",
+            package
+        ),
+    }
+}
+
+fn xcode_hint() -> String {
+    match target_lexicon::HOST.operating_system {
+        OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_) => {
+            "install the Xcode command line tools with `xcode-select --install`".to_string()
+        }
+        _ => "apple bundles can only be signed and packaged on macOS".to_string(),
+    }
+}
@@ -0,0 +1,430 @@
+use crate::cli::{Error, Result};
+
+const SHT_DYNAMIC: u32 = 6;
+const SHT_DYNSYM: u32 = 11;
+const SHT_GNU_VERNEED: u32 = 0x6fff_fffe;
+const SHT_GNU_VERSYM: u32 = 0x6fff_ffff;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+
+/// A symbol version required from a shared library, e.g. `GLIBC_2.34` from `libc.so.6`.
+#[derive(Debug, Clone)]
+pub struct ElfVersionNeed {
+    pub library: String,
+    pub version: String,
+    /// The undefined symbols bound to this version
+    pub symbols: Vec<String>,
+}
+
+/// Just enough of an ELF reader for the dynamic linking information of a shared library.
+pub struct ElfFile<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    is_le: bool,
+}
+
+struct Section {
+    kind: u32,
+    offset: usize,
+    size: usize,
+    link: usize,
+    info: usize,
+    entry_size: usize,
+}
+
+struct Symbol {
+    name: String,
+    defined: bool,
+    exported: bool,
+}
+
+impl<'a> ElfFile<'a> {
+    pub fn is_elf(data: &[u8]) -> bool {
+        data.starts_with(b"\x7fELF")
+    }
+
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        if !Self::is_elf(data) || data.len() < 64 {
+            return Err(Error::new("not an ELF file"));
+        }
+
+        Ok(Self {
+            data,
+            is_64: data[4] == 2,
+            is_le: data[5] == 1,
+        })
+    }
+
+    pub fn version_needs(&self) -> Result<Vec<ElfVersionNeed>> {
+        let sections = self.sections()?;
+        let Some(verneed) = sections.iter().find(|x| x.kind == SHT_GNU_VERNEED) else {
+            return Ok(vec![]);
+        };
+        let strings = self.section_at(&sections, verneed.link)?;
+
+        // version index -> (library, version)
+        let mut needs = Vec::new();
+        let mut offset = verneed.offset;
+        for _ in 0..verneed.info {
+            let count = self.u16(add(offset, 2)?)?;
+            let library = self.string(strings, self.u32(add(offset, 4)?)? as usize)?;
+
+            let mut aux = add(offset, self.u32(add(offset, 8)?)? as usize)?;
+            for _ in 0..count {
+                let index = self.u16(add(aux, 6)?)? & 0x7fff;
+                let version = self.string(strings, self.u32(add(aux, 8)?)? as usize)?;
+                needs.push((
+                    index,
+                    ElfVersionNeed {
+                        library: library.clone(),
+                        version,
+                        symbols: vec![],
+                    },
+                ));
+
+                aux = add(aux, self.u32(add(aux, 12)?)? as usize)?;
+            }
+
+            let next = self.u32(add(offset, 12)?)? as usize;
+            if next == 0 {
+                break;
+            }
+            offset = add(offset, next)?;
+        }
+
+        let symbols = self.dynamic_symbols(&sections)?;
+        if let Some(versym) = sections.iter().find(|x| x.kind == SHT_GNU_VERSYM) {
+            for (i, symbol) in symbols.iter().enumerate() {
+                if symbol.defined || symbol.name.is_empty() {
+                    continue;
+                }
+
+                let index = self.u16(add(versym.offset, mul(i, 2)?)?)? & 0x7fff;
+                if let Some((_, need)) = needs.iter_mut().find(|x| x.0 == index) {
+                    need.symbols.push(symbol.name.clone());
+                }
+            }
+        }
+
+        Ok(needs.into_iter().map(|x| x.1).collect())
+    }
+
+    /// The symbols other libraries can link against or look up with `dlsym`.
+    pub fn exports(&self) -> Result<Vec<String>> {
+        let sections = self.sections()?;
+        Ok(self
+            .dynamic_symbols(&sections)?
+            .into_iter()
+            .filter(|x| x.exported && !x.name.is_empty())
+            .map(|x| x.name)
+            .collect())
+    }
+
+    /// The `DT_NEEDED` entries, the shared libraries loaded together with this one.
+    pub fn needed(&self) -> Result<Vec<String>> {
+        let sections = self.sections()?;
+        let Some(dynamic) = sections.iter().find(|x| x.kind == SHT_DYNAMIC) else {
+            return Ok(vec![]);
+        };
+        let strings = self.section_at(&sections, dynamic.link)?;
+        let entry_size = if self.is_64 { 16 } else { 8 };
+
+        let mut needed = Vec::new();
+        for i in 0..dynamic.size / entry_size {
+            let offset = add(dynamic.offset, i * entry_size)?;
+            let (tag, value) = match self.is_64 {
+                true => (self.u64(offset)?, self.u64(add(offset, 8)?)?),
+                false => (self.u32(offset)? as u64, self.u32(add(offset, 4)?)? as u64),
+            };
+
+            match tag {
+                DT_NULL => break,
+                DT_NEEDED => needed.push(self.string(strings, value as usize)?),
+                _ => {}
+            }
+        }
+
+        Ok(needed)
+    }
+
+    fn dynamic_symbols(&self, sections: &[Section]) -> Result<Vec<Symbol>> {
+        let Some(dynsym) = sections.iter().find(|x| x.kind == SHT_DYNSYM) else {
+            return Ok(vec![]);
+        };
+        let strings = self.section_at(sections, dynsym.link)?;
+        let entry_size = match dynsym.entry_size {
+            0 if self.is_64 => 24,
+            0 => 16,
+            size => size,
+        };
+
+        let mut symbols = Vec::new();
+        for i in 0..dynsym.size / entry_size {
+            let offset = add(dynsym.offset, i * entry_size)?;
+            let (info, other, section_index) = match self.is_64 {
+                true => (
+                    self.u8(add(offset, 4)?)?,
+                    self.u8(add(offset, 5)?)?,
+                    self.u16(add(offset, 6)?)?,
+                ),
+                false => (
+                    self.u8(add(offset, 12)?)?,
+                    self.u8(add(offset, 13)?)?,
+                    self.u16(add(offset, 14)?)?,
+                ),
+            };
+
+            // global or weak binding with default or protected visibility
+            let defined = section_index != 0;
+            symbols.push(Symbol {
+                name: self.string(strings, self.u32(offset)? as usize)?,
+                defined,
+                exported: defined && matches!(info >> 4, 1 | 2) && matches!(other & 3, 0 | 3),
+            });
+        }
+
+        Ok(symbols)
+    }
+
+    fn sections(&self) -> Result<Vec<Section>> {
+        let (offset, entry_size, count) = match self.is_64 {
+            true => (self.u64(0x28)?, self.u16(0x3a)?, self.u16(0x3c)?),
+            false => (self.u32(0x20)? as u64, self.u16(0x2e)?, self.u16(0x30)?),
+        };
+
+        (0..count as usize)
+            .map(|i| {
+                let header = add(offset as usize, i * entry_size as usize)?;
+                Ok(match self.is_64 {
+                    true => Section {
+                        kind: self.u32(add(header, 4)?)?,
+                        offset: self.u64(add(header, 24)?)? as usize,
+                        size: self.u64(add(header, 32)?)? as usize,
+                        link: self.u32(add(header, 40)?)? as usize,
+                        info: self.u32(add(header, 44)?)? as usize,
+                        entry_size: self.u64(add(header, 56)?)? as usize,
+                    },
+                    false => Section {
+                        kind: self.u32(add(header, 4)?)?,
+                        offset: self.u32(add(header, 16)?)? as usize,
+                        size: self.u32(add(header, 20)?)? as usize,
+                        link: self.u32(add(header, 24)?)? as usize,
+                        info: self.u32(add(header, 28)?)? as usize,
+                        entry_size: self.u32(add(header, 36)?)? as usize,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    fn section_at<'s>(&self, sections: &'s [Section], index: usize) -> Result<&'s Section> {
+        sections
+            .get(index)
+            .ok_or_else(|| Error::new("invalid ELF section index"))
+    }
+
+    fn string(&self, table: &Section, offset: usize) -> Result<String> {
+        let start = add(table.offset, offset)?;
+        let bytes = self.bytes(start, table.size.saturating_sub(offset))?;
+        let end = bytes.iter().position(|x| *x == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        self.data
+            .get(offset..offset.saturating_add(len))
+            .ok_or_else(|| Error::new("truncated ELF file"))
+    }
+
+    fn u8(&self, offset: usize) -> Result<u8> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.bytes(offset, 2)?.try_into().unwrap_or_default();
+        Ok(match self.is_le {
+            true => u16::from_le_bytes(bytes),
+            false => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset, 4)?.try_into().unwrap_or_default();
+        Ok(match self.is_le {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64> {
+        let bytes = self.bytes(offset, 8)?.try_into().unwrap_or_default();
+        Ok(match self.is_le {
+            true => u64::from_le_bytes(bytes),
+            false => u64::from_be_bytes(bytes),
+        })
+    }
+}
+
+/// `base + offset` for offsets read from the file, which can be anything in a malformed one.
+fn add(base: usize, offset: usize) -> Result<usize> {
+    base.checked_add(offset)
+        .ok_or_else(|| Error::new("truncated ELF file"))
+}
+
+fn mul(index: usize, size: usize) -> Result<usize> {
+    index
+        .checked_mul(size)
+        .ok_or_else(|| Error::new("truncated ELF file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::util::fixture::{put_u16, put_u32, put_u64};
+
+    const SHT_STRTAB: u32 = 3;
+
+    const STRINGS: &[u8] =
+        b"\0libc.so.6\0GLIBC_2.2.5\0GLIBC_2.34\0memcpy\0pthread_create\0clap_entry\0helper\0\
+          libasound.so.2\0";
+
+    /// The offset of `name` in [`STRINGS`].
+    fn name(name: &str) -> u32 {
+        let needle = format!("\0{}\0", name);
+        let index = STRINGS
+            .windows(needle.len())
+            .position(|x| x == needle.as_bytes())
+            .unwrap();
+        index as u32 + 1
+    }
+
+    /// A little endian ELF64 file with the sections `(kind, link, info, contents)` after the
+    /// null section.
+    fn build_elf(sections: &[(u32, u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut data = vec![0; 64];
+        data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+
+        let mut headers = vec![0; 64];
+        for (kind, link, info, contents) in sections {
+            let mut header = [0; 64];
+            put_u32(&mut header, 4, *kind);
+            put_u64(&mut header, 24, data.len() as u64);
+            put_u64(&mut header, 32, contents.len() as u64);
+            put_u32(&mut header, 40, *link);
+            put_u32(&mut header, 44, *info);
+            headers.extend(header);
+            data.extend(contents);
+        }
+
+        let offset = data.len() as u64;
+        put_u64(&mut data, 0x28, offset);
+        put_u16(&mut data, 0x3a, 64);
+        put_u16(&mut data, 0x3c, sections.len() as u16 + 1);
+        data.extend(headers);
+        data
+    }
+
+    /// An `Elf64_Sym`, `section` is 0 for undefined symbols.
+    fn symbol(name: u32, info: u8, other: u8, section: u16) -> Vec<u8> {
+        let mut symbol = vec![0; 24];
+        put_u32(&mut symbol, 0, name);
+        symbol[4] = info;
+        symbol[5] = other;
+        put_u16(&mut symbol, 6, section);
+        symbol
+    }
+
+    /// A library that loads `libc.so.6` and `libasound.so.2`, needs `memcpy@GLIBC_2.2.5` and
+    /// `pthread_create@GLIBC_2.34` and defines `clap_entry` and a hidden `helper`.
+    fn sample() -> Vec<u8> {
+        let symbols = [
+            symbol(0, 0, 0, 0),
+            symbol(name("memcpy"), 0x12, 0, 0),
+            symbol(name("pthread_create"), 0x12, 0, 0),
+            symbol(name("clap_entry"), 0x11, 0, 1),
+            symbol(name("helper"), 0x12, 2, 1),
+        ]
+        .concat();
+
+        let versym = [0u16, 2, 3, 1, 1].map(u16::to_le_bytes).concat();
+
+        // one Elf64_Verneed for libc.so.6: version, count, file, aux and next
+        let mut verneed = Vec::new();
+        verneed.extend([1u16, 2].map(u16::to_le_bytes).concat());
+        verneed.extend([name("libc.so.6"), 16, 0].map(u32::to_le_bytes).concat());
+
+        // followed by its Elf64_Vernaux: hash, flags, version index, name and next
+        for (index, version, next) in [(2, "GLIBC_2.2.5", 16), (3, "GLIBC_2.34", 0)] {
+            verneed.extend(0u32.to_le_bytes());
+            verneed.extend([0u16, index].map(u16::to_le_bytes).concat());
+            verneed.extend([name(version), next].map(u32::to_le_bytes).concat());
+        }
+
+        // everything after DT_NULL is padding
+        let dynamic = [
+            (DT_NEEDED, name("libc.so.6") as u64),
+            (DT_NEEDED, name("libasound.so.2") as u64),
+            (DT_NULL, 0),
+            (DT_NEEDED, name("helper") as u64),
+        ]
+        .map(|(tag, value)| [tag, value].map(u64::to_le_bytes).concat())
+        .concat();
+
+        build_elf(&[
+            (SHT_STRTAB, 0, 0, STRINGS.to_vec()),
+            (SHT_DYNSYM, 1, 1, symbols),
+            (SHT_GNU_VERSYM, 2, 0, versym),
+            (SHT_GNU_VERNEED, 1, 1, verneed),
+            (SHT_DYNAMIC, 1, 0, dynamic),
+        ])
+    }
+
+    #[test]
+    fn test_version_needs() {
+        let data = sample();
+        let needs = ElfFile::parse(&data).unwrap().version_needs().unwrap();
+        let needs = needs
+            .iter()
+            .map(|x| (x.library.as_str(), x.version.as_str(), x.symbols.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            needs,
+            [
+                ("libc.so.6", "GLIBC_2.2.5", vec!["memcpy".to_string()]),
+                (
+                    "libc.so.6",
+                    "GLIBC_2.34",
+                    vec!["pthread_create".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exports() {
+        let data = sample();
+        let exports = ElfFile::parse(&data).unwrap().exports().unwrap();
+        assert_eq!(exports, ["clap_entry"]);
+    }
+
+    #[test]
+    fn test_malformed_offsets() {
+        // point the dynamic symbols, the second section, at the end of the address space
+        let mut data = sample();
+        let headers = u64::from_le_bytes(data[0x28..0x30].try_into().unwrap()) as usize;
+        put_u64(&mut data, headers + 2 * 64 + 24, u64::MAX - 4);
+
+        let file = ElfFile::parse(&data).unwrap();
+        assert!(file.exports().is_err());
+        assert!(file.version_needs().is_err());
+    }
+
+    #[test]
+    fn test_needed() {
+        let data = sample();
+        let needed = ElfFile::parse(&data).unwrap().needed().unwrap();
+        assert_eq!(needed, ["libc.so.6", "libasound.so.2"]);
+    }
+}
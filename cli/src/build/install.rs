@@ -0,0 +1,228 @@
+use super::{PluginFormat, plugin_system_folder, reflink, wait_unlink};
+use crate::cli::{Error, Result, report_message, report_span};
+use owo_colors::OwoColorize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+use target_lexicon::OperatingSystem;
+
+/// Records which bundles in an install folder were put there by us, one `<package> <file>` per
+/// line, so uninstalling never touches plugins installed by anything else.
+const MANIFEST_FILE: &str = ".picobundler-installed";
+
+/// Serializes manifest updates, bundles are installed from multiple threads.
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InstallMode {
+    /// Copies the bundle, using a reflink where the filesystem supports it
+    #[default]
+    Copy,
+    /// Links to the bundle in the output directory, so rebuilding updates the installed plugin
+    Symlink,
+}
+
+impl FromStr for InstallMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "copy" => Ok(InstallMode::Copy),
+            "symlink" => Ok(InstallMode::Symlink),
+            _ => Err(
+                Error::new(format!("unknown install mode {}", s.bold())).with_note(format!(
+                    "use either {} or {}",
+                    "copy".bold().bright_cyan(),
+                    "symlink".bold().bright_green()
+                )),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InstallScope {
+    /// Plugin folders in the home directory
+    #[default]
+    User,
+    /// Plugin folders shared by all users, usually requiring elevated permissions
+    System,
+}
+
+/// Where `--install` puts bundles.
+#[derive(Debug, Clone)]
+pub struct InstallLocation {
+    pub scope: InstallScope,
+    /// Subfolder of the plugin folder, empty to install into the plugin folder itself
+    pub subfolder: String,
+    /// Used instead of the plugin folder for every format
+    pub dir: Option<PathBuf>,
+    /// Used instead of the plugin folder for a single format, takes precedence over `dir`
+    pub format_dirs: HashMap<PluginFormat, PathBuf>,
+}
+
+impl Default for InstallLocation {
+    fn default() -> Self {
+        Self {
+            scope: InstallScope::User,
+            subfolder: "dev".to_string(),
+            dir: None,
+            format_dirs: HashMap::new(),
+        }
+    }
+}
+
+impl InstallLocation {
+    /// Explicit directories are used as is, otherwise the bundle goes into the subfolder of
+    /// the plugin folder of `os`.
+    pub fn folder(&self, format: PluginFormat, os: OperatingSystem) -> Result<PathBuf> {
+        if let Some(dir) = self.explicit_dir(format) {
+            return Ok(dir.to_path_buf());
+        }
+
+        let folder = plugin_system_folder(format, os, self.scope)?;
+        if self.subfolder.is_empty() {
+            Ok(folder)
+        } else {
+            Ok(folder.join(&self.subfolder))
+        }
+    }
+
+    pub fn explicit_dir(&self, format: PluginFormat) -> Option<&Path> {
+        self.format_dirs
+            .get(&format)
+            .or(self.dir.as_ref())
+            .map(|x| x.as_path())
+    }
+}
+
+pub fn install_bundle(
+    bundle: &Path,
+    folder: &Path,
+    file_name: &str,
+    package: &str,
+    mode: InstallMode,
+) -> Result<PathBuf> {
+    let install_path = folder.join(file_name);
+
+    match mode {
+        InstallMode::Copy => {
+            let _ = std::fs::create_dir_all(&install_path);
+            wait_unlink(&install_path)?;
+            reflink(bundle, &install_path)?;
+        }
+        InstallMode::Symlink => {
+            let bundle = std::path::absolute(bundle)?;
+
+            // keep a link that is already up to date, hosts may be watching it
+            if std::fs::read_link(&install_path).ok().as_ref() != Some(&bundle) {
+                let _ = std::fs::create_dir_all(&install_path);
+                wait_unlink(&install_path)?;
+                symlink(&bundle, &install_path)?;
+            }
+        }
+    }
+
+    let _lock = MANIFEST_LOCK.lock().unwrap();
+    let mut entries = read_manifest(folder)?;
+    if !entries.iter().any(|(_, x)| x == file_name) {
+        entries.push((package.to_string(), file_name.to_string()));
+        write_manifest(folder, &entries)?;
+    }
+
+    Ok(install_path)
+}
+
+/// Removes the bundles of `packages` (or of every package if `None`) from an install folder and
+/// returns the removed paths.
+pub fn uninstall_bundles(folder: &Path, packages: Option<&[String]>) -> Result<Vec<PathBuf>> {
+    report_span!("uninstalling from {}", folder.display().bold());
+
+    let _lock = MANIFEST_LOCK.lock().unwrap();
+    let entries = read_manifest(folder)?;
+
+    let mut removed = Vec::new();
+    let mut kept = Vec::new();
+    for (package, file_name) in entries {
+        if packages.is_some_and(|x| !x.contains(&package)) {
+            kept.push((package, file_name));
+            continue;
+        }
+
+        let path = folder.join(&file_name);
+        // a link whose bundle is gone is still ours to remove
+        if path.symlink_metadata().is_ok() {
+            wait_unlink(&path)?;
+            removed.push(path);
+        }
+    }
+
+    if packages.is_none() {
+        for entry in std::fs::read_dir(folder).into_iter().flatten().flatten() {
+            if entry.file_name() != MANIFEST_FILE {
+                report_message!(
+                    "skipping {}, it was not installed by picobundler",
+                    entry.path().display()
+                );
+            }
+        }
+    }
+
+    if kept.is_empty() {
+        wait_unlink(&folder.join(MANIFEST_FILE))?;
+    } else {
+        write_manifest(folder, &kept)?;
+    }
+
+    Ok(removed)
+}
+
+fn symlink(src: &Path, dst: &Path) -> Result<()> {
+    report_span!("linking {} to {}", dst.display(), src.display());
+
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(src, dst);
+
+    #[cfg(windows)]
+    let result = if src.is_dir() {
+        std::os::windows::fs::symlink_dir(src, dst)
+    } else {
+        std::os::windows::fs::symlink_file(src, dst)
+    };
+
+    result.map_err(|e| {
+        let error = Error::from(e);
+        if cfg!(windows) {
+            error.with_note("creating symlinks on Windows requires developer mode to be enabled")
+        } else {
+            error
+        }
+    })
+}
+
+fn read_manifest(folder: &Path) -> Result<Vec<(String, String)>> {
+    let manifest = match std::fs::read_to_string(folder.join(MANIFEST_FILE)) {
+        Ok(x) => x,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(manifest
+        .lines()
+        .filter_map(|x| x.split_once(' '))
+        .map(|(package, file_name)| (package.to_string(), file_name.to_string()))
+        .collect())
+}
+
+fn write_manifest(folder: &Path, entries: &[(String, String)]) -> Result<()> {
+    let manifest = entries
+        .iter()
+        .map(|(package, file_name)| format!("{} {}\n", package, file_name))
+        .collect::<String>();
+
+    std::fs::write(folder.join(MANIFEST_FILE), manifest)?;
+    Ok(())
+}
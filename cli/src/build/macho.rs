@@ -0,0 +1,343 @@
+use crate::cli::{Error, Result};
+
+const MH_MAGIC: u32 = 0xfeed_face;
+const MH_MAGIC_64: u32 = 0xfeed_facf;
+const FAT_MAGIC: u32 = 0xcafe_babe;
+const FAT_MAGIC_64: u32 = 0xcafe_babf;
+/// Java class files share `FAT_MAGIC`, their version takes the place of the slice count and is at
+/// least 45, while universal binaries have a handful of slices.
+const MAX_FAT_ARCHS: u32 = 20;
+
+const LC_SYMTAB: u32 = 0x2;
+/// `LC_LOAD_DYLIB`, `LC_LOAD_WEAK_DYLIB`, `LC_REEXPORT_DYLIB`, `LC_LAZY_LOAD_DYLIB` and
+/// `LC_LOAD_UPWARD_DYLIB`
+const LC_LOAD_DYLIBS: &[u32] = &[0xc, 0x8000_0018, 0x8000_001f, 0x20, 0x8000_0023];
+
+const N_STAB: u8 = 0xe0;
+const N_PEXT: u8 = 0x10;
+const N_TYPE: u8 = 0x0e;
+const N_SECT: u8 = 0x0e;
+const N_EXT: u8 = 0x01;
+
+/// Just enough of a Mach-O reader for the exports and dylibs of a single architecture slice.
+pub struct MachOFile<'a> {
+    data: &'a [u8],
+    is_64: bool,
+}
+
+impl<'a> MachOFile<'a> {
+    pub fn is_macho(data: &[u8]) -> bool {
+        let Some(magic) = data.get(..4) else {
+            return false;
+        };
+
+        let magic = magic.try_into().unwrap_or_default();
+        if matches!(u32::from_le_bytes(magic), MH_MAGIC | MH_MAGIC_64) {
+            return true;
+        }
+
+        matches!(u32::from_be_bytes(magic), FAT_MAGIC | FAT_MAGIC_64)
+            && be_u32(data, 4).is_ok_and(|x| (1..MAX_FAT_ARCHS).contains(&x))
+    }
+
+    /// The slices of a universal binary, or the binary itself.
+    pub fn parse(data: &'a [u8]) -> Result<Vec<Self>> {
+        let magic = be_u32(data, 0)?;
+        if !matches!(magic, FAT_MAGIC | FAT_MAGIC_64) {
+            return Ok(vec![Self::parse_slice(data)?]);
+        }
+
+        // fat headers are big endian, fat_arch_64 has 64 bit offsets and sizes
+        let (entry_size, is_64) = match magic {
+            FAT_MAGIC_64 => (32, true),
+            _ => (20, false),
+        };
+
+        (0..be_u32(data, 4)? as usize)
+            .map(|i| {
+                let entry = add(8, mul(i, entry_size)?)?;
+                let (offset, size) = match is_64 {
+                    true => (
+                        be_u64(data, add(entry, 8)?)?,
+                        be_u64(data, add(entry, 16)?)?,
+                    ),
+                    false => (
+                        be_u32(data, add(entry, 8)?)? as u64,
+                        be_u32(data, add(entry, 12)?)? as u64,
+                    ),
+                };
+
+                let slice = data
+                    .get(offset as usize..offset.saturating_add(size) as usize)
+                    .ok_or_else(|| Error::new("truncated Mach-O file"))?;
+                Self::parse_slice(slice)
+            })
+            .collect()
+    }
+
+    fn parse_slice(data: &'a [u8]) -> Result<Self> {
+        let file = Self { data, is_64: false };
+        match file.u32(0)? {
+            MH_MAGIC => Ok(file),
+            MH_MAGIC_64 => Ok(Self { data, is_64: true }),
+            _ => Err(Error::new("not a Mach-O file")),
+        }
+    }
+
+    /// The external symbols defined in a section, without the leading underscore.
+    pub fn exports(&self) -> Result<Vec<String>> {
+        let Some(symtab) = self
+            .load_commands()?
+            .into_iter()
+            .find(|x| x.0 == LC_SYMTAB)
+            .map(|x| x.1)
+        else {
+            return Ok(vec![]);
+        };
+
+        let symbols = self.u32(add(symtab, 8)?)? as usize;
+        let count = self.u32(add(symtab, 12)?)? as usize;
+        let strings = self.u32(add(symtab, 16)?)? as usize;
+        let entry_size = if self.is_64 { 16 } else { 12 };
+
+        let mut exports = Vec::new();
+        for i in 0..count {
+            let entry = add(symbols, mul(i, entry_size)?)?;
+            let kind = self.bytes(add(entry, 4)?, 1)?[0];
+            if kind & N_STAB != 0
+                || kind & N_PEXT != 0
+                || kind & N_EXT == 0
+                || kind & N_TYPE != N_SECT
+            {
+                continue;
+            }
+
+            let name = self.string(add(strings, self.u32(entry)? as usize)?)?;
+            exports.push(name.strip_prefix('_').unwrap_or(&name).to_string());
+        }
+
+        Ok(exports)
+    }
+
+    /// The install names of the dylibs this binary loads, e.g. `/usr/lib/libSystem.B.dylib`.
+    pub fn dylibs(&self) -> Result<Vec<String>> {
+        self.load_commands()?
+            .into_iter()
+            .filter(|x| LC_LOAD_DYLIBS.contains(&x.0))
+            .map(|(_, offset)| self.string(add(offset, self.u32(add(offset, 8)?)? as usize)?))
+            .collect()
+    }
+
+    /// The kind and file offset of every load command.
+    fn load_commands(&self) -> Result<Vec<(u32, usize)>> {
+        let count = self.u32(16)?;
+        let mut offset = if self.is_64 { 32 } else { 28 };
+
+        let mut commands = Vec::new();
+        for _ in 0..count {
+            let size = self.u32(add(offset, 4)?)? as usize;
+            commands.push((self.u32(offset)?, offset));
+
+            if size == 0 {
+                return Err(Error::new("invalid Mach-O load command"));
+            }
+            offset = add(offset, size)?;
+        }
+
+        Ok(commands)
+    }
+
+    fn string(&self, offset: usize) -> Result<String> {
+        let bytes = self
+            .data
+            .get(offset..)
+            .ok_or_else(|| Error::new("truncated Mach-O file"))?;
+        let end = bytes.iter().position(|x| *x == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        self.data
+            .get(offset..offset.saturating_add(len))
+            .ok_or_else(|| Error::new("truncated Mach-O file"))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset, 4)?.try_into().unwrap_or_default();
+        Ok(u32::from_le_bytes(bytes))
+    }
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset.saturating_add(4))
+        .ok_or_else(|| Error::new("truncated Mach-O file"))?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap_or_default()))
+}
+
+fn be_u64(data: &[u8], offset: usize) -> Result<u64> {
+    let bytes = data
+        .get(offset..offset.saturating_add(8))
+        .ok_or_else(|| Error::new("truncated Mach-O file"))?;
+    Ok(u64::from_be_bytes(bytes.try_into().unwrap_or_default()))
+}
+
+/// `base + offset` for offsets read from the file, which can be anything in a malformed one.
+fn add(base: usize, offset: usize) -> Result<usize> {
+    base.checked_add(offset)
+        .ok_or_else(|| Error::new("truncated Mach-O file"))
+}
+
+fn mul(index: usize, size: usize) -> Result<usize> {
+    index
+        .checked_mul(size)
+        .ok_or_else(|| Error::new("truncated Mach-O file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::util::fixture::put_u32;
+
+    /// A dylib that loads `dylibs`, with a symbol table of `(name, n_type)`.
+    fn build_macho(is_64: bool, symbols: &[(&str, u8)], dylibs: &[&str]) -> Vec<u8> {
+        let (magic, header_size, entry_size) = match is_64 {
+            true => (MH_MAGIC_64, 32, 16),
+            false => (MH_MAGIC, 28, 12),
+        };
+
+        // LC_LOAD_DYLIB with the install name after the 24 byte command, padded to 8 bytes
+        let mut commands = Vec::new();
+        for dylib in dylibs {
+            let mut command = vec![0; (24 + dylib.len() + 1).next_multiple_of(8)];
+            let size = command.len() as u32;
+            put_u32(&mut command, 0, LC_LOAD_DYLIBS[0]);
+            put_u32(&mut command, 4, size);
+            put_u32(&mut command, 8, 24);
+            command[24..24 + dylib.len()].copy_from_slice(dylib.as_bytes());
+            commands.extend(command);
+        }
+
+        let symbol_offset = header_size + commands.len() + 24;
+        let string_offset = symbol_offset + symbols.len() * entry_size;
+
+        let mut table = Vec::new();
+        let mut strings = vec![0];
+        for (name, kind) in symbols {
+            let mut entry = vec![0; entry_size];
+            put_u32(&mut entry, 0, strings.len() as u32);
+            entry[4] = *kind;
+            table.extend(entry);
+            strings.extend(name.bytes().chain([0]));
+        }
+
+        let mut data = vec![0; header_size];
+        put_u32(&mut data, 0, magic);
+        put_u32(&mut data, 12, 6);
+        put_u32(&mut data, 16, dylibs.len() as u32 + 1);
+        put_u32(&mut data, 20, commands.len() as u32 + 24);
+        data.extend(commands);
+
+        // LC_SYMTAB
+        let symtab = [
+            LC_SYMTAB,
+            24,
+            symbol_offset as u32,
+            symbols.len() as u32,
+            string_offset as u32,
+            strings.len() as u32,
+        ];
+        data.extend(symtab.map(u32::to_le_bytes).concat());
+
+        data.extend(table);
+        data.extend(strings);
+        data
+    }
+
+    /// A universal binary of the slices, with 64 bit offsets and sizes if `is_64`.
+    fn build_fat(is_64: bool, slices: &[Vec<u8>]) -> Vec<u8> {
+        let (magic, entry_size) = match is_64 {
+            true => (FAT_MAGIC_64, 32),
+            false => (FAT_MAGIC, 20),
+        };
+
+        let mut data = [magic, slices.len() as u32].map(u32::to_be_bytes).concat();
+        let mut offset = 8 + slices.len() * entry_size;
+        for slice in slices {
+            let mut entry = vec![0; entry_size];
+            match is_64 {
+                true => {
+                    entry[8..16].copy_from_slice(&(offset as u64).to_be_bytes());
+                    entry[16..24].copy_from_slice(&(slice.len() as u64).to_be_bytes());
+                }
+                false => {
+                    entry[8..12].copy_from_slice(&(offset as u32).to_be_bytes());
+                    entry[12..16].copy_from_slice(&(slice.len() as u32).to_be_bytes());
+                }
+            }
+
+            data.extend(entry);
+            offset += slice.len();
+        }
+
+        data.extend(slices.concat());
+        data
+    }
+
+    const SYMBOLS: &[(&str, u8)] = &[
+        ("_clap_entry", N_SECT | N_EXT),
+        ("__ZN6plugin7processEv", N_SECT | N_EXT),
+        ("_private_extern", N_SECT | N_EXT | N_PEXT),
+        ("_local", N_SECT),
+        ("_malloc", N_EXT),
+        ("plugin.c", 0x64),
+    ];
+
+    fn exports(data: &[u8]) -> Vec<Vec<String>> {
+        MachOFile::parse(data)
+            .unwrap()
+            .iter()
+            .map(|x| x.exports().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_exports() {
+        let expected = ["clap_entry", "_ZN6plugin7processEv"];
+
+        let thin = build_macho(true, SYMBOLS, &[]);
+        assert!(MachOFile::is_macho(&thin));
+        assert_eq!(exports(&thin), [expected]);
+
+        let slices = [
+            build_macho(true, SYMBOLS, &[]),
+            build_macho(false, &SYMBOLS[..1], &[]),
+        ];
+        for is_64 in [false, true] {
+            let fat = build_fat(is_64, &slices);
+            assert!(MachOFile::is_macho(&fat));
+            assert_eq!(exports(&fat), [&expected[..], &["clap_entry"]]);
+        }
+
+        // a Java class file of version 52.0
+        assert!(!MachOFile::is_macho(&[0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 52]));
+    }
+
+    #[test]
+    fn test_dylibs() {
+        let system = "/usr/lib/libSystem.B.dylib";
+        let asound = "/opt/homebrew/lib/libasound.2.dylib";
+        let data = build_macho(true, SYMBOLS, &[system, asound]);
+
+        let slices = MachOFile::parse(&data).unwrap();
+        assert_eq!(slices[0].dylibs().unwrap(), [system, asound]);
+        assert_eq!(
+            slices[0].exports().unwrap(),
+            ["clap_entry", "_ZN6plugin7processEv"]
+        );
+
+        let data = build_fat(false, &[build_macho(false, &[], &[system])]);
+        let slices = MachOFile::parse(&data).unwrap();
+        assert_eq!(slices[0].dylibs().unwrap(), [system]);
+    }
+}
@@ -1,38 +1,72 @@
 mod apple;
+mod audit;
 mod cache;
 mod cargo;
+mod clap;
 mod cmake;
+mod doctor;
+mod elf;
+mod install;
+mod macho;
+mod pe;
 mod util;
+mod validator;
+mod watch;
 mod zig;
 
 pub use apple::*;
+pub use audit::*;
 pub use cargo::*;
+pub use clap::*;
+pub use cmake::CmakeBuildType;
+pub use doctor::*;
+pub use install::*;
 pub use util::*;
+pub use validator::*;
+pub use watch::*;
+pub use zig::zig_cc;
 
 use crate::{
-    cli::{Error, Result},
+    cli::{Error, Result, report_warning},
     report_span,
 };
 use cache::{Dependency, DependencyCache};
 use cmake::{ClapWrapperOptions, build_wrapper, ensure_cmake_installed};
 use owo_colors::OwoColorize;
+use std::{fmt::Display, panic::resume_unwind};
 use std::{
-    collections::HashMap,
     path::{Path, PathBuf},
     str::FromStr,
+    time::{Duration, Instant},
 };
-use std::{fmt::Display, panic::resume_unwind};
-use target_lexicon::{OperatingSystem, Triple};
-use tinyjson::JsonValue;
-use zig::{ensure_zig_installed, zig_triple};
+use target_lexicon::{Environment, OperatingSystem, Triple};
+use zig::{ensure_zig_installed, zig_linker, zig_triple};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Vst3Sdk {
     OpenSource,
     Proprietary,
     Local(PathBuf),
 }
 
+impl FromStr for Vst3Sdk {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "gpl" => Ok(Self::OpenSource),
+            "proprietary" => Ok(Self::Proprietary),
+            _ => Err(
+                Error::new(format!("unknown VST3 SDK {}", s.bold())).with_note(format!(
+                    "use either {} or {}",
+                    "gpl".bold().bright_cyan(),
+                    "proprietary".bold().bright_green()
+                )),
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum BuildTarget {
     Triple(Triple),
@@ -58,6 +92,36 @@ impl BuildTarget {
         }
     }
 
+    /// Targets other than the host are cross compiled with zig.
+    pub fn needs_zig(&self) -> bool {
+        match self {
+            Self::Triple(triple) => triple != &target_lexicon::HOST,
+            Self::TripleGlibc(_, _) => true,
+            Self::AppleUniversal => !matches!(
+                target_lexicon::HOST.operating_system,
+                OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_)
+            ),
+        }
+    }
+
+    /// zig can't stand in for the msvc linker, so these targets are only linked by the cmake build
+    /// when cross compiled.
+    pub fn needs_cmake_link(&self) -> bool {
+        self.needs_zig()
+            && matches!(self, Self::Triple(triple) if triple.environment == Environment::Msvc)
+    }
+
+    /// The rust targets that have to be installed to build this target.
+    pub fn rust_triples(&self) -> Vec<Triple> {
+        match self {
+            Self::Triple(triple) | Self::TripleGlibc(triple, _) => vec![triple.clone()],
+            Self::AppleUniversal => ["aarch64-apple-darwin", "x86_64-apple-darwin"]
+                .into_iter()
+                .filter_map(|x| Triple::from_str(x).ok())
+                .collect(),
+        }
+    }
+
     pub fn operating_system(&self) -> OperatingSystem {
         match self {
             Self::Triple(triple) => triple.operating_system.clone(),
@@ -122,49 +186,139 @@ impl PluginFormat {
     }
 }
 
+impl FromStr for PluginFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "clap" => Ok(Self::Clap),
+            "vst3" => Ok(Self::Vst3),
+            "auv2" => Ok(Self::Auv2),
+            _ => Err(Error::new(format!("unknown plugin format {}", s.bold()))
+                .with_note("supported formats are clap, vst3 and auv2")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildPackage {
+    pub name: String,
+
+    pub display_name: Option<String>,
+    pub bundle_id: Option<String>,
+    pub vendor: Option<String>,
+
+    pub targets: Vec<BuildTarget>,
+    pub features: Vec<String>,
+
+    pub clap: bool,
+    pub auv2: bool,
+    pub vst3: Option<Vst3Sdk>,
+
+    /// The oldest glibc the Linux binaries have to run on
+    pub min_glibc: Option<String>,
+    /// Libraries the binaries may link against on top of the ones every system has
+    pub allowed_libraries: Vec<String>,
+    pub unknown_libraries: UnknownLibraries,
+}
+
+impl BuildPackage {
+    pub fn output_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+
+    /// The wrapper links the plugin for vst3/auv2, and for targets zig can't link.
+    fn use_cmake(&self) -> bool {
+        self.vst3.is_some() || self.auv2 || self.targets.iter().any(|x| x.needs_cmake_link())
+    }
+
+    /// Names the cmake build tree of a wrapper build, e.g. `release-clap-vst3`.
+    fn wrapper_config_name(&self, profile: &str) -> String {
+        let mut name = profile.to_string();
+        for (format, enabled) in [
+            ("clap", true),
+            ("vst3", self.vst3.is_some()),
+            ("auv2", self.auv2),
+        ] {
+            if enabled {
+                name.push('-');
+                name.push_str(format);
+            }
+        }
+
+        name
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BuildRequest {
     pub target_dir: PathBuf,
+    pub cmake_dir: Option<PathBuf>,
 
-    pub packages: Vec<String>,
+    pub packages: Vec<BuildPackage>,
     pub profile: String,
-    pub targets: Vec<BuildTarget>,
+    pub build_type: Option<CmakeBuildType>,
 
-    pub features: Vec<String>,
     pub all_features: bool,
     pub no_default_features: bool,
+    /// Run `rustup target add` for missing targets instead of failing
+    pub install_targets: bool,
 
-    pub clap: bool,
-    pub auv2: bool,
-    pub vst3: Option<Vst3Sdk>,
+    /// How many packages and targets are built at the same time
+    pub jobs: usize,
 }
 
 pub struct BuildArtifact {
     pub package: String,
+    pub name: String,
     pub target: BuildTarget,
     pub format: PluginFormat,
     pub path: PathBuf,
+    /// Time spent building and wrapping the target of the artifact
+    pub duration: Duration,
+    /// The newest glibc and libstdc++ versions the binaries need, for Linux targets
+    pub glibc: Option<GlibcVersions>,
+    /// The shared libraries the binaries load
+    pub libraries: Vec<LibraryDependency>,
 }
 
 pub fn build(request: &BuildRequest) -> Result<Vec<BuildArtifact>> {
+    let package_names = request
+        .packages
+        .iter()
+        .map(|x| x.name.clone())
+        .collect::<Vec<_>>();
+
     report_span!(
         "building plugins: {}",
-        request.packages.join(", ").bold().bright_blue()
+        package_names.join(", ").bold().bright_blue()
     );
 
-    if request.vst3.is_none() && !request.auv2 && !request.clap {
+    let packages = request
+        .packages
+        .iter()
+        .filter(|x| x.clap || x.auv2 || x.vst3.is_some())
+        .collect::<Vec<_>>();
+
+    if packages.is_empty() {
         return Ok(vec![]);
     }
 
-    let use_zig = request.targets.iter().any(|x| match x {
-        BuildTarget::Triple(triple) => triple != &target_lexicon::HOST,
-        BuildTarget::TripleGlibc(_, _) => true,
-        BuildTarget::AppleUniversal => !matches!(
-            target_lexicon::HOST.operating_system,
-            OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_)
-        ),
-    });
-    let use_cmake = request.vst3.is_some() || request.auv2 || use_zig;
+    let mut targets = Vec::<&BuildTarget>::new();
+    for target in packages.iter().flat_map(|x| &x.targets) {
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+
+    let use_zig = targets.iter().any(|x| x.needs_zig());
+    let use_cmake = packages.iter().any(|x| x.use_cmake());
+
+    let triples = targets
+        .iter()
+        .flat_map(|x| x.rust_triples())
+        .collect::<Vec<_>>();
+    ensure_rust_targets(&triples, request.install_targets)?;
 
     if use_zig {
         ensure_zig_installed()?;
@@ -174,81 +328,178 @@ pub fn build(request: &BuildRequest) -> Result<Vec<BuildArtifact>> {
         ensure_cmake_installed()?;
     }
 
-    if !use_cmake {
-        let artifacts = build_libraries(
-            CargoCrateType::Cdylib,
-            request.target_dir.clone(),
-            request.profile.clone(),
-            request.packages.clone(),
-            request.targets.clone(),
-            request.features.clone(),
-            request.all_features,
-            request.no_default_features,
+    let dependencies = if use_cmake {
+        let mut vst3_sdks = Vec::<&Vst3Sdk>::new();
+        for sdk in packages.iter().filter_map(|x| x.vst3.as_ref()) {
+            if !vst3_sdks.contains(&sdk) {
+                vst3_sdks.push(sdk);
+            }
+        }
+
+        let (pico_cmake, vst3_paths) = load_dependencies(
+            &vst3_sdks,
+            request.cmake_dir.as_deref(),
+            &request.target_dir,
         )?;
 
-        return Ok(artifacts
-            .into_iter()
-            .map(|artifact| BuildArtifact {
+        Some((
+            pico_cmake,
+            vst3_sdks.into_iter().zip(vst3_paths).collect::<Vec<_>>(),
+        ))
+    } else {
+        None
+    };
+
+    // every package is built and wrapped for every target on its own, so wrapping one overlaps
+    // with compiling the next
+    let jobs = packages
+        .iter()
+        .flat_map(|package| package.targets.iter().map(move |target| (*package, target)))
+        .collect::<Vec<_>>();
+
+    let built = run_parallel_limited(jobs, request.jobs, |(package, target), cancel| {
+        report_span!(
+            "building {} for {}",
+            package.name.bold(),
+            target.to_string().bold().bright_blue()
+        );
+        let started = Instant::now();
+
+        let crate_type = match package.use_cmake() {
+            true => CargoCrateType::Staticlib,
+            false => CargoCrateType::Cdylib,
+        };
+
+        let artifacts = build_libraries(request, package, cancel, crate_type, target)?;
+        let (Some((pico_cmake, vst3_sdks)), CargoCrateType::Staticlib) =
+            (&dependencies, crate_type)
+        else {
+            let output = artifacts
+                .into_iter()
+                .map(|artifact| BuildArtifact {
+                    name: package.output_name().to_string(),
+                    package: artifact.package,
+                    target: artifact.target,
+                    format: PluginFormat::Clap,
+                    path: artifact.path,
+                    duration: started.elapsed(),
+                    glibc: None,
+                    libraries: vec![],
+                })
+                .collect::<Vec<_>>();
+
+            return audit_artifacts(package, output);
+        };
+
+        let vst3_sdk = package
+            .vst3
+            .as_ref()
+            .and_then(|sdk| vst3_sdks.iter().find(|x| x.0 == sdk).map(|x| x.1.clone()));
+
+        let mut output = Vec::new();
+        for artifact in artifacts {
+            cancel.check()?;
+
+            let name = package.output_name().to_string();
+
+            // the profile cargo built with, which includes overrides from outside the manifest
+            let build_type = match (request.build_type, &artifact.profile) {
+                (Some(build_type), _) => build_type,
+                (None, Some(profile)) => profile.cmake_build_type(),
+                (None, None) => {
+                    report_warning!(
+                        "cargo didn't report the profile of {}, building the wrapper as {} (see {})",
+                        artifact.package,
+                        CmakeBuildType::Release,
+                        "cmake-build-type".bold()
+                    );
+                    CmakeBuildType::Release
+                }
+            };
+
+            // every wrapper build gets its own cmake build tree, so they can run at the same time
+            let build_dir = request
+                .target_dir
+                .join("clap-wrapper/build")
+                .join(&artifact.package)
+                .join(artifact.target.to_string())
+                .join(package.wrapper_config_name(&request.profile));
+
+            let clap_wrapper = build_wrapper(ClapWrapperOptions {
+                cmake_dir: pico_cmake.clone(),
+                build_dir,
+                build_type,
+                package_name: artifact.package.clone(),
+                output_name: name.clone(),
+                bundle_id: package.bundle_id.clone(),
+                vendor: package.vendor.clone(),
+                static_lib: artifact.path,
+                zig_triple: artifact.zig_triple,
+                osx_arch: artifact.osx_arch,
+                native_static_libs: artifact.native_static_libs,
+                vst3: vst3_sdk.clone(),
+                auv2: package.auv2,
+            })?;
+            let duration = started.elapsed();
+
+            if let Some(vst3) = clap_wrapper.vst3 {
+                output.push(BuildArtifact {
+                    package: artifact.package.clone(),
+                    name: name.clone(),
+                    target: artifact.target.clone(),
+                    format: PluginFormat::Vst3,
+                    path: vst3,
+                    duration,
+                    glibc: None,
+                    libraries: vec![],
+                });
+            }
+            if let Some(auv2) = clap_wrapper.auv2 {
+                output.push(BuildArtifact {
+                    package: artifact.package.clone(),
+                    name: name.clone(),
+                    target: artifact.target.clone(),
+                    format: PluginFormat::Auv2,
+                    path: auv2,
+                    duration,
+                    glibc: None,
+                    libraries: vec![],
+                });
+            }
+
+            output.push(BuildArtifact {
                 package: artifact.package,
+                name,
                 target: artifact.target,
                 format: PluginFormat::Clap,
-                path: artifact.path,
-            })
-            .collect());
-    }
-
-    let mut output = Vec::new();
-    let (pico_cmake, vst3_sdk) = load_dependencies(request.vst3.as_ref(), &request.target_dir)?;
-    let artifacts = build_libraries(
-        CargoCrateType::Staticlib,
-        request.target_dir.clone(),
-        request.profile.clone(),
-        request.packages.clone(),
-        request.targets.clone(),
-        request.features.clone(),
-        request.all_features,
-        request.no_default_features,
-    )?;
-
-    for artifact in artifacts {
-        let clap_wrapper = build_wrapper(ClapWrapperOptions {
-            cmake_dir: pico_cmake.clone(),
-            build_dir: request.target_dir.join("clap-wrapper/build"),
-            package_name: artifact.package.clone(),
-            static_lib: artifact.path,
-            zig_triple: artifact.zig_triple,
-            osx_arch: artifact.osx_arch,
-            native_static_libs: artifact.native_static_libs,
-            vst3: vst3_sdk.clone(),
-            auv2: request.auv2,
-        })?;
-
-        if let Some(vst3) = clap_wrapper.vst3 {
-            output.push(BuildArtifact {
-                package: artifact.package.clone(),
-                target: artifact.target.clone(),
-                format: PluginFormat::Vst3,
-                path: vst3,
-            });
-        }
-        if let Some(auv2) = clap_wrapper.auv2 {
-            output.push(BuildArtifact {
-                package: artifact.package.clone(),
-                target: artifact.target.clone(),
-                format: PluginFormat::Auv2,
-                path: auv2,
+                path: clap_wrapper.clap,
+                duration,
+                glibc: None,
+                libraries: vec![],
             });
         }
 
-        output.push(BuildArtifact {
-            package: artifact.package,
-            target: artifact.target,
-            format: PluginFormat::Clap,
-            path: clap_wrapper.clap,
-        });
+        audit_artifacts(package, output)
+    })?;
+
+    Ok(built.into_iter().flatten().collect())
+}
+
+fn audit_artifacts(
+    package: &BuildPackage,
+    mut artifacts: Vec<BuildArtifact>,
+) -> Result<Vec<BuildArtifact>> {
+    for artifact in &mut artifacts {
+        artifact.glibc = audit_glibc_versions(artifact, package.min_glibc.as_deref())?;
+        audit_exports(artifact)?;
+        artifact.libraries = audit_libraries(
+            artifact,
+            &package.allowed_libraries,
+            package.unknown_libraries,
+        )?;
     }
 
-    Ok(output)
+    Ok(artifacts)
 }
 
 struct IntermediateArtifact {
@@ -257,149 +508,178 @@ struct IntermediateArtifact {
     path: PathBuf,
 
     native_static_libs: Option<String>,
+    profile: Option<CargoProfile>,
     zig_triple: Option<String>,
     osx_arch: Option<String>,
 }
 
-#[allow(clippy::too_many_arguments)]
+fn cargo_build_target(
+    request: &BuildRequest,
+    package: &BuildPackage,
+    cancel: &Cancellation,
+    crate_type: CargoCrateType,
+    target: Triple,
+    zig_triple: Option<&str>,
+) -> Result<Vec<CargoArtifact>> {
+    // a static library is linked by the cmake build, which gets the zig triple itself
+    let linker = match (crate_type, zig_triple) {
+        (CargoCrateType::Cdylib, Some(zig_triple)) => {
+            Some(zig_linker(&request.target_dir, zig_triple)?)
+        }
+        _ => None,
+    };
+
+    cancel.check()?;
+
+    // cargo locks its target directory for the whole build, so every target gets its own to be
+    // compiled at the same time as the others
+    let target_dir = request.target_dir.join("cargo").join(target.to_string());
+
+    cargo_build(CargoBuild {
+        crate_type,
+        target_dir,
+        packages: vec![package.name.clone()],
+        profile: request.profile.clone(),
+        target,
+        features: package.features.clone(),
+        all_features: request.all_features,
+        no_default_features: request.no_default_features,
+        linker,
+    })
+}
+
 fn build_libraries(
+    request: &BuildRequest,
+    package: &BuildPackage,
+    cancel: &Cancellation,
     crate_type: CargoCrateType,
-    target_dir: PathBuf,
-    profile: String,
-    packages: Vec<String>,
-    targets: Vec<BuildTarget>,
-    features: Vec<String>,
-    all_features: bool,
-    no_default_features: bool,
+    target: &BuildTarget,
 ) -> Result<Vec<IntermediateArtifact>> {
     let mut output = Vec::new();
-    for target in targets {
-        match &target {
-            BuildTarget::Triple(triple) => {
-                let osx_arch = match triple.architecture {
-                    _ if matches!(triple.operating_system, OperatingSystem::MacOSX(_)) => None,
-                    target_lexicon::Architecture::Aarch64(_) => Some("arm64".to_string()),
-                    target_lexicon::Architecture::X86_64 => Some("x86_64".to_string()),
-                    _ => None,
-                };
-
-                let zig_triple = if target.is_supported(&target_lexicon::HOST) {
-                    None
-                } else {
-                    Some(zig_triple(triple, None)?)
-                };
-
-                let artifacts = cargo_build(CargoBuild {
-                    crate_type,
-                    target_dir: target_dir.clone(),
-                    packages: packages.clone(),
-                    profile: profile.clone(),
-                    target: triple.clone(),
-                    features: features.clone(),
-                    all_features,
-                    no_default_features,
-                })?;
-
-                for artifact in artifacts {
-                    output.push(IntermediateArtifact {
-                        package: artifact.package,
-                        target: target.clone(),
-                        path: artifact.path,
-                        native_static_libs: artifact.native_static_libs,
-                        zig_triple: zig_triple.clone(),
-                        osx_arch: osx_arch.clone(),
-                    });
-                }
+    match target {
+        BuildTarget::Triple(triple) => {
+            let osx_arch = match triple.architecture {
+                _ if matches!(triple.operating_system, OperatingSystem::MacOSX(_)) => None,
+                target_lexicon::Architecture::Aarch64(_) => Some("arm64".to_string()),
+                target_lexicon::Architecture::X86_64 => Some("x86_64".to_string()),
+                _ => None,
+            };
+
+            // the same check `build` uses to require zig, e.g. a musl target on a gnu host too
+            let zig_triple = match target.needs_zig() {
+                true => Some(zig_triple(triple, None)?),
+                false => None,
+            };
+
+            let artifacts = cargo_build_target(
+                request,
+                package,
+                cancel,
+                crate_type,
+                triple.clone(),
+                zig_triple.as_deref(),
+            )?;
+
+            for artifact in artifacts {
+                output.push(IntermediateArtifact {
+                    package: artifact.package,
+                    target: target.clone(),
+                    path: artifact.path,
+                    native_static_libs: artifact.native_static_libs,
+                    profile: artifact.profile,
+                    zig_triple: zig_triple.clone(),
+                    osx_arch: osx_arch.clone(),
+                });
             }
+        }
 
-            BuildTarget::TripleGlibc(triple, glibc) => {
-                let zig_triple = zig_triple(triple, Some(glibc))?;
-
-                let artifacts = cargo_build(CargoBuild {
-                    crate_type,
-                    target_dir: target_dir.clone(),
-                    packages: packages.clone(),
-                    profile: profile.clone(),
-                    target: triple.clone(),
-                    features: features.clone(),
-                    all_features,
-                    no_default_features,
-                })?;
-
-                for artifact in artifacts {
-                    output.push(IntermediateArtifact {
-                        package: artifact.package,
-                        target: target.clone(),
-                        path: artifact.path,
-                        native_static_libs: artifact.native_static_libs,
-                        zig_triple: Some(zig_triple.clone()),
-                        osx_arch: None,
-                    });
-                }
+        BuildTarget::TripleGlibc(triple, glibc) => {
+            let zig_triple = zig_triple(triple, Some(glibc))?;
+
+            let artifacts = cargo_build_target(
+                request,
+                package,
+                cancel,
+                crate_type,
+                triple.clone(),
+                Some(&zig_triple),
+            )?;
+
+            for artifact in artifacts {
+                output.push(IntermediateArtifact {
+                    package: artifact.package,
+                    target: target.clone(),
+                    path: artifact.path,
+                    native_static_libs: artifact.native_static_libs,
+                    profile: artifact.profile,
+                    zig_triple: Some(zig_triple.clone()),
+                    osx_arch: None,
+                });
             }
+        }
 
-            BuildTarget::AppleUniversal => {
-                let mut output_aarch64 = cargo_build(CargoBuild {
-                    crate_type,
-                    target_dir: target_dir.clone(),
-                    packages: packages.clone(),
-                    profile: profile.clone(),
-                    target: Triple::from_str("aarch64-apple-darwin")?,
-                    features: features.clone(),
-                    all_features,
-                    no_default_features,
-                })?
-                .into_iter()
-                .map(|x| (x.package.clone(), x))
-                .collect::<HashMap<_, _>>();
-
-                let mut output_x86_64 = cargo_build(CargoBuild {
-                    crate_type,
-                    target_dir: target_dir.clone(),
-                    packages: packages.clone(),
-                    profile: profile.clone(),
-                    target: Triple::from_str("x86_64-apple-darwin")?,
-                    features: features.clone(),
-                    all_features,
-                    no_default_features,
-                })?
-                .into_iter()
-                .map(|x| (x.package.clone(), x))
-                .collect::<HashMap<_, _>>();
-
-                for package in &packages {
-                    let aarch64 = output_aarch64.remove(package);
-                    let x86_64 = output_x86_64.remove(package);
-
-                    if let (Some(aarch64), Some(x86_64)) = (aarch64, x86_64) {
-                        let universal = target_dir.join("universal-apple-darwin");
-                        let _ = std::fs::create_dir_all(&universal);
-
-                        let universal =
-                            universal.join(aarch64.path.file_name().unwrap_or_default());
-                        apple::lipo(&[&aarch64.path, &x86_64.path], &universal)?;
-
-                        output.push(IntermediateArtifact {
-                            target: target.clone(),
-                            package: aarch64.package,
-                            path: universal,
-                            native_static_libs: aarch64.native_static_libs,
-                            zig_triple: None,
-                            osx_arch: Some("x86_64;arm64".to_string()),
-                        })
-                    }
-                }
+        BuildTarget::AppleUniversal => {
+            let aarch64 = Triple::from_str("aarch64-apple-darwin")?;
+            let x86_64 = Triple::from_str("x86_64-apple-darwin")?;
+
+            let (zig_aarch64, zig_x86_64) = match target.needs_zig() {
+                true => (
+                    Some(zig_triple(&aarch64, None)?),
+                    Some(zig_triple(&x86_64, None)?),
+                ),
+                false => (None, None),
+            };
+
+            let output_aarch64 = cargo_build_target(
+                request,
+                package,
+                cancel,
+                crate_type,
+                aarch64,
+                zig_aarch64.as_deref(),
+            )?;
+
+            let output_x86_64 = cargo_build_target(
+                request,
+                package,
+                cancel,
+                crate_type,
+                x86_64,
+                zig_x86_64.as_deref(),
+            )?;
+
+            let aarch64 = output_aarch64.into_iter().next();
+            let x86_64 = output_x86_64.into_iter().next();
+
+            if let (Some(aarch64), Some(x86_64)) = (aarch64, x86_64) {
+                let universal = request.target_dir.join("universal-apple-darwin");
+                let _ = std::fs::create_dir_all(&universal);
+
+                let universal = universal.join(aarch64.path.file_name().unwrap_or_default());
+                apple::lipo(&[&aarch64.path, &x86_64.path], &universal)?;
+
+                output.push(IntermediateArtifact {
+                    target: target.clone(),
+                    package: aarch64.package,
+                    path: universal,
+                    native_static_libs: aarch64.native_static_libs,
+                    profile: aarch64.profile,
+                    zig_triple: None,
+                    osx_arch: Some("x86_64;arm64".to_string()),
+                })
             }
-        };
-    }
+        }
+    };
 
     Ok(output)
 }
 
+/// Loads the cmake project and every vst3 sdk in `vst3`, the sdk paths are returned in order.
 fn load_dependencies(
-    vst3: Option<&Vst3Sdk>,
+    vst3: &[&Vst3Sdk],
+    cmake_dir: Option<&Path>,
     target_dir: &Path,
-) -> Result<(PathBuf, Option<PathBuf>)> {
+) -> Result<(PathBuf, Vec<PathBuf>)> {
     let cache = DependencyCache::new(target_dir.join("clap-wrapper/deps"));
 
     fn unwrap_thread<T>(result: std::thread::Result<T>) -> T {
@@ -409,51 +689,49 @@ fn load_dependencies(
         }
     }
 
+    let cache = &cache;
     std::thread::scope(|scope| {
-        let vst3 = scope.spawn(|| -> Result<Option<PathBuf>> {
-            Ok(match vst3 {
-                Some(Vst3Sdk::OpenSource) => Some(cache.load(&Dependency::Vst3OSS(
-                    "8b59557d881bb0158ba08ff256b26f025f078314".to_string(),
-                ))?),
-                Some(Vst3Sdk::Proprietary) => Some(cache.load(&Dependency::Vst3Proprietary)?),
-                Some(Vst3Sdk::Local(path)) => {
-                    if !path.exists() {
-                        return Err(Error::new(format!(
-                            "{} not found at {}",
-                            "vst3-sdk".bright_cyan().bold(),
-                            std::fs::canonicalize(path)?.display()
-                        ))
-                        .with_note(
-                            "you've specified a local path to the sdk, but the path doesn't exist",
-                        ));
-                    }
-
-                    Some(path.clone())
-                }
-                None => None,
-            })
-        });
+        let vst3 = vst3
+            .iter()
+            .map(|sdk| scope.spawn(move || load_vst3_sdk(cache, sdk)))
+            .collect::<Vec<_>>();
 
         let pico = scope.spawn(|| -> Result<PathBuf> {
-            let cargo_workspace = cargo_workspace_dir()?;
-            let cargo_local_cmake = cargo_metadata()?
-                .get("metadata")
-                .and_then(|x| x.get::<HashMap<String, JsonValue>>())
-                .and_then(|x| x.get("picobundler"))
-                .and_then(|x| x.get::<HashMap<String, JsonValue>>())
-                .and_then(|x| x.get("local-cmake-path"))
-                .and_then(|x| x.get::<String>().cloned())
-                .map(|x| cargo_workspace.join(x));
-
-            match cargo_local_cmake {
-                Some(path) => Ok(path),
+            match cmake_dir {
+                Some(path) => Ok(path.to_path_buf()),
                 None => Ok(cache.load(&Dependency::SelfCmake(env!("GIT_HASH").to_string()))?),
             }
         });
 
-        let vst3 = unwrap_thread(vst3.join())?;
+        let vst3 = vst3
+            .into_iter()
+            .map(|x| unwrap_thread(x.join()))
+            .collect::<Result<Vec<_>>>()?;
         let pico = unwrap_thread(pico.join())?;
 
         Ok((pico, vst3))
     })
 }
+
+fn load_vst3_sdk(cache: &DependencyCache, sdk: &Vst3Sdk) -> Result<PathBuf> {
+    match sdk {
+        Vst3Sdk::OpenSource => cache.load(&Dependency::Vst3OSS(
+            "8b59557d881bb0158ba08ff256b26f025f078314".to_string(),
+        )),
+        Vst3Sdk::Proprietary => cache.load(&Dependency::Vst3Proprietary),
+        Vst3Sdk::Local(path) => {
+            if !path.exists() {
+                return Err(Error::new(format!(
+                    "{} not found at {}",
+                    "vst3-sdk".bright_cyan().bold(),
+                    std::fs::canonicalize(path)?.display()
+                ))
+                .with_note(
+                    "you've specified a local path to the sdk, but the path doesn't exist",
+                ));
+            }
+
+            Ok(path.clone())
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::cli::{Error, Result};
+
+/// Just enough of a PE reader for the exports and imports of a DLL.
+pub struct PeFile<'a> {
+    data: &'a [u8],
+    /// File offset of the data directories
+    directories: usize,
+    directory_count: usize,
+    sections: Vec<Section>,
+}
+
+struct Section {
+    address: u32,
+    size: u32,
+    offset: u32,
+}
+
+impl<'a> PeFile<'a> {
+    /// Plenty of other files start with `MZ`, so the `PE\0\0` signature has to follow too.
+    pub fn is_pe(data: &[u8]) -> bool {
+        let header = data
+            .get(0x3c..0x40)
+            .map(|x| u32::from_le_bytes(x.try_into().unwrap_or_default()) as usize);
+
+        data.starts_with(b"MZ")
+            && header.and_then(|x| data.get(x..x.checked_add(4)?)) == Some(b"PE\0\0".as_slice())
+    }
+
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let mut file = Self {
+            data,
+            directories: 0,
+            directory_count: 0,
+            sections: vec![],
+        };
+
+        if !Self::is_pe(data) {
+            return Err(Error::new("not a PE file"));
+        }
+
+        let header = file.u32(0x3c)? as usize;
+
+        let coff = header + 4;
+        let section_count = file.u16(coff + 2)? as usize;
+        let optional = coff + 20;
+        let optional_size = file.u16(coff + 16)? as usize;
+
+        // PE32 or PE32+
+        let (directories, directory_count) = match file.u16(optional)? {
+            0x10b => (optional + 96, file.u32(optional + 92)?),
+            0x20b => (optional + 112, file.u32(optional + 108)?),
+            _ => return Err(Error::new("unknown PE optional header")),
+        };
+
+        file.directories = directories;
+        file.directory_count = directory_count as usize;
+
+        let table = optional + optional_size;
+        for i in 0..section_count {
+            let section = table + i * 40;
+            file.sections.push(Section {
+                size: file.u32(section + 8)?.max(file.u32(section + 16)?),
+                address: file.u32(section + 12)?,
+                offset: file.u32(section + 20)?,
+            });
+        }
+
+        Ok(file)
+    }
+
+    /// The names in the export directory.
+    pub fn exports(&self) -> Result<Vec<String>> {
+        let Some(directory) = self.directory(0)? else {
+            return Ok(vec![]);
+        };
+
+        let count = self.u32(add(directory, 24)?)?;
+        let names = self.offset(self.u32(add(directory, 32)?)?)?;
+
+        (0..count as usize)
+            .map(|i| self.string(self.offset(self.u32(add(names, mul(i, 4)?)?)?)?))
+            .collect()
+    }
+
+    /// The DLLs in the import and delay import directories.
+    pub fn imports(&self) -> Result<Vec<String>> {
+        let mut imports = Vec::new();
+
+        // (directory, descriptor size, offset of the name in the descriptor)
+        for (index, entry_size, name) in [(1, 20, 12), (13, 32, 4)] {
+            let Some(directory) = self.directory(index)? else {
+                continue;
+            };
+
+            // the descriptors end with an all zero one
+            let mut entry = directory;
+            loop {
+                match self.u32(add(entry, name)?)? {
+                    0 => break,
+                    address => imports.push(self.string(self.offset(address)?)?),
+                }
+                entry = add(entry, entry_size)?;
+            }
+        }
+
+        Ok(imports)
+    }
+
+    /// The file offset of a data directory, if the binary has one.
+    fn directory(&self, index: usize) -> Result<Option<usize>> {
+        if index >= self.directory_count {
+            return Ok(None);
+        }
+
+        match self.u32(self.directories + index * 8)? {
+            0 => Ok(None),
+            address => self.offset(address).map(Some),
+        }
+    }
+
+    /// Maps a relative virtual address to the file offset.
+    fn offset(&self, address: u32) -> Result<usize> {
+        self.sections
+            .iter()
+            .find(|x| address >= x.address && address - x.address < x.size)
+            .ok_or_else(|| Error::new("invalid PE address"))
+            .and_then(|x| add(x.offset as usize, (address - x.address) as usize))
+    }
+
+    fn string(&self, offset: usize) -> Result<String> {
+        let bytes = self
+            .data
+            .get(offset..)
+            .ok_or_else(|| Error::new("truncated PE file"))?;
+        let end = bytes.iter().position(|x| *x == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        self.data
+            .get(offset..offset.saturating_add(len))
+            .ok_or_else(|| Error::new("truncated PE file"))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.bytes(offset, 2)?.try_into().unwrap_or_default();
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset, 4)?.try_into().unwrap_or_default();
+        Ok(u32::from_le_bytes(bytes))
+    }
+}
+
+/// `base + offset` for offsets read from the file, which can be anything in a malformed one.
+fn add(base: usize, offset: usize) -> Result<usize> {
+    base.checked_add(offset)
+        .ok_or_else(|| Error::new("truncated PE file"))
+}
+
+fn mul(index: usize, size: usize) -> Result<usize> {
+    index
+        .checked_mul(size)
+        .ok_or_else(|| Error::new("truncated PE file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::util::fixture::{put_u16, put_u32};
+
+    /// Where the contents of the only section start in the file and in memory.
+    const SECTION_OFFSET: usize = 0x200;
+    const SECTION_ADDRESS: u32 = 0x1000;
+
+    /// A PE32+ file with one section, the data `directories` are `(index, offset in contents)`.
+    fn build_pe(directories: &[(usize, usize)], contents: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; SECTION_OFFSET];
+        data[..2].copy_from_slice(b"MZ");
+        put_u32(&mut data, 0x3c, 0x40);
+        data[0x40..0x44].copy_from_slice(b"PE\0\0");
+
+        // COFF header: x86-64, one section and the size of the optional header
+        put_u16(&mut data, 0x44, 0x8664);
+        put_u16(&mut data, 0x46, 1);
+        put_u16(&mut data, 0x54, 240);
+
+        // optional header with 16 data directories, then the section table
+        put_u16(&mut data, 0x58, 0x20b);
+        put_u32(&mut data, 0x58 + 108, 16);
+        for (index, offset) in directories {
+            let directory = 0x58 + 112 + index * 8;
+            put_u32(&mut data, directory, SECTION_ADDRESS + *offset as u32);
+            put_u32(&mut data, directory + 4, (contents.len() - offset) as u32);
+        }
+
+        let section = 0x58 + 240;
+        put_u32(&mut data, section + 8, contents.len() as u32);
+        put_u32(&mut data, section + 12, SECTION_ADDRESS);
+        put_u32(&mut data, section + 16, contents.len() as u32);
+        put_u32(&mut data, section + 20, SECTION_OFFSET as u32);
+
+        data.extend(contents);
+        data
+    }
+
+    /// Writes the strings at `offset` and returns their addresses.
+    fn put_strings(contents: &mut [u8], mut offset: usize, strings: &[&str]) -> Vec<u32> {
+        let mut addresses = Vec::new();
+        for string in strings {
+            contents[offset..offset + string.len()].copy_from_slice(string.as_bytes());
+            addresses.push(SECTION_ADDRESS + offset as u32);
+            offset += string.len() + 1;
+        }
+        addresses
+    }
+
+    #[test]
+    fn test_exports() {
+        let mut contents = vec![0; 0x100];
+        let names = put_strings(
+            &mut contents,
+            0x80,
+            &["GetPluginFactory", "?process@@YAXXZ"],
+        );
+
+        // the export directory and its array of name addresses
+        put_u32(&mut contents, 24, names.len() as u32);
+        put_u32(&mut contents, 32, SECTION_ADDRESS + 0x40);
+        for (i, name) in names.iter().enumerate() {
+            put_u32(&mut contents, 0x40 + i * 4, *name);
+        }
+
+        let data = build_pe(&[(0, 0)], &contents);
+        assert!(PeFile::is_pe(&data));
+        assert!(!PeFile::is_pe(b"MZ, but a text file rather than a binary"));
+
+        let file = PeFile::parse(&data).unwrap();
+        assert_eq!(
+            file.exports().unwrap(),
+            ["GetPluginFactory", "?process@@YAXXZ"]
+        );
+
+        let data = build_pe(&[], &contents);
+        let file = PeFile::parse(&data).unwrap();
+        assert_eq!(file.exports().unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_imports() {
+        let mut contents = vec![0; 0x100];
+        let names = put_strings(
+            &mut contents,
+            0x80,
+            &["KERNEL32.dll", "asound.dll", "dwmapi.dll"],
+        );
+
+        // two import descriptors with the name at 12 and a delay import descriptor with the name
+        // at 4, both directories end with an all zero descriptor
+        put_u32(&mut contents, 12, names[0]);
+        put_u32(&mut contents, 20 + 12, names[1]);
+        put_u32(&mut contents, 0x40 + 4, names[2]);
+
+        let data = build_pe(&[(1, 0), (13, 0x40)], &contents);
+        let file = PeFile::parse(&data).unwrap();
+        assert_eq!(
+            file.imports().unwrap(),
+            ["KERNEL32.dll", "asound.dll", "dwmapi.dll"]
+        );
+
+        let data = build_pe(&[(1, 0)], &contents);
+        let file = PeFile::parse(&data).unwrap();
+        assert_eq!(file.imports().unwrap(), ["KERNEL32.dll", "asound.dll"]);
+    }
+}
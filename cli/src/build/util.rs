@@ -1,5 +1,5 @@
-use super::PluginFormat;
-use crate::cli::{Command, Error, Result, report_span};
+use super::{InstallScope, PluginFormat};
+use crate::cli::{Command, Error, Result, remove_on_interrupt, report_span};
 use owo_colors::OwoColorize;
 use std::{
     env::var,
@@ -7,6 +7,10 @@ use std::{
     io::ErrorKind,
     panic::resume_unwind,
     path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 use target_lexicon::OperatingSystem;
 
@@ -39,8 +43,88 @@ where
     })
 }
 
+/// Handed to every item of [`run_parallel_limited`], so long running items can stop between
+/// steps once another item failed.
+pub struct Cancellation {
+    cancelled: AtomicBool,
+}
+
+impl Cancellation {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Fails if another item failed, the error is never reported.
+    pub fn check(&self) -> Result<()> {
+        match self.is_cancelled() {
+            true => Err(Error::new("cancelled")),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Like [`run_parallel`], but runs at most `jobs` items at the same time. Once an item fails, the
+/// items that haven't started yet are skipped and the error of the first failed item is returned.
+pub fn run_parallel_limited<I, O>(
+    items: I,
+    jobs: usize,
+    f: impl Fn(I::Item, &Cancellation) -> Result<O> + Send + Sync,
+) -> Result<Vec<O>>
+where
+    I: IntoIterator,
+    O: Send,
+    I::Item: Send,
+{
+    let items = items.into_iter().enumerate().collect::<Vec<_>>();
+    let workers = jobs.clamp(1, items.len().max(1));
+
+    let queue = Mutex::new(items.into_iter());
+    let results = Mutex::new(Vec::new());
+    let error = Mutex::new(None);
+    let cancellation = Cancellation {
+        cancelled: AtomicBool::new(false),
+    };
+
+    std::thread::scope(|s| {
+        let mut handles = Vec::new();
+        for _ in 0..workers {
+            handles.push(s.spawn(|| {
+                while !cancellation.is_cancelled() {
+                    let Some((index, item)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+
+                    match f(item, &cancellation) {
+                        Ok(result) => results.lock().unwrap().push((index, result)),
+                        Err(e) => {
+                            if !cancellation.cancelled.swap(true, Ordering::Relaxed) {
+                                *error.lock().unwrap() = Some(e);
+                            }
+                        }
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            if let Err(e) = handle.join() {
+                resume_unwind(e);
+            }
+        }
+    });
+
+    if let Some(e) = error.into_inner().unwrap() {
+        return Err(e);
+    }
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
 pub fn reflink(src: &Path, dst: &Path) -> Result<()> {
     report_span!("copying {} to {}", src.display(), dst.display());
+    let _cleanup = remove_on_interrupt(dst);
 
     if fs::metadata(src)?.is_file() {
         reflink::reflink_or_copy(src, dst)?;
@@ -68,11 +152,31 @@ pub fn reflink(src: &Path, dst: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Total size of the files of a bundle in bytes, links are not followed.
+pub fn bundle_size(path: &Path) -> Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += bundle_size(&entry?.path())?;
+    }
+
+    Ok(size)
+}
+
 pub fn wait_unlink(dst: &Path) -> Result<()> {
     report_span!("removing {}", dst.display());
 
     let try_remove = || {
-        if fs::metadata(dst)?.is_file() {
+        let metadata = fs::symlink_metadata(dst)?;
+        if metadata.is_symlink() {
+            // removes the link itself, never what it points to. links to directories are
+            // directories themselves on windows
+            fs::remove_file(dst).or_else(|_| fs::remove_dir(dst))
+        } else if metadata.is_file() {
             fs::remove_file(dst)
         } else {
             fs::remove_dir_all(dst)
@@ -98,34 +202,52 @@ pub fn wait_unlink(dst: &Path) -> Result<()> {
     }
 }
 
-pub fn plugin_system_folder(plugin: PluginFormat, os: OperatingSystem) -> Result<PathBuf> {
-    let path = match (plugin, os) {
-        (PluginFormat::Clap, OperatingSystem::Windows) => var("PROGRAMFILES")
+pub fn plugin_system_folder(
+    plugin: PluginFormat,
+    os: OperatingSystem,
+    scope: InstallScope,
+) -> Result<PathBuf> {
+    let is_apple = matches!(os, OperatingSystem::MacOSX(_) | OperatingSystem::Darwin(_));
+    let apple_folder = |name: &str| match scope {
+        InstallScope::User => var("HOME")
+            .map(|x| format!("{}/Library/Audio/Plug-Ins/{}/", x, name))
+            .ok(),
+        InstallScope::System => Some(format!("/Library/Audio/Plug-Ins/{}/", name)),
+    };
+
+    let path = match (plugin, os, scope) {
+        (PluginFormat::Clap, OperatingSystem::Windows, _) => var("PROGRAMFILES")
             .map(|x| format!("{}/Common Files/CLAP/", x))
             .ok(),
-        (PluginFormat::Clap, OperatingSystem::Linux) => {
-            var("HOME").map(|x| format!("{}/.clap/", x)).ok()
+        (PluginFormat::Clap, OperatingSystem::Linux, InstallScope::User) => {
+            // the first entry of CLAP_PATH takes precedence over ~/.clap
+            match var("CLAP_PATH")
+                .ok()
+                .and_then(|x| std::env::split_paths(&x).next())
+            {
+                Some(path) if !path.as_os_str().is_empty() => {
+                    Some(path.to_string_lossy().to_string())
+                }
+                _ => var("HOME").map(|x| format!("{}/.clap/", x)).ok(),
+            }
         }
-        (PluginFormat::Clap, OperatingSystem::MacOSX(_))
-        | (PluginFormat::Clap, OperatingSystem::Darwin(_)) => var("HOME")
-            .map(|x| format!("{}/Library/Audio/Plug-Ins/CLAP/", x))
-            .ok(),
+        (PluginFormat::Clap, OperatingSystem::Linux, InstallScope::System) => {
+            Some("/usr/lib/clap/".to_string())
+        }
+        (PluginFormat::Clap, _, _) if is_apple => apple_folder("CLAP"),
 
-        (PluginFormat::Vst3, OperatingSystem::Windows) => var("PROGRAMFILES")
+        (PluginFormat::Vst3, OperatingSystem::Windows, _) => var("PROGRAMFILES")
             .map(|x| format!("{}/Common Files/VST3/", x))
             .ok(),
-        (PluginFormat::Vst3, OperatingSystem::Linux) => {
+        (PluginFormat::Vst3, OperatingSystem::Linux, InstallScope::User) => {
             var("HOME").map(|x| format!("{}/.vst3/", x)).ok()
         }
-        (PluginFormat::Vst3, OperatingSystem::MacOSX(_))
-        | (PluginFormat::Vst3, OperatingSystem::Darwin(_)) => var("HOME")
-            .map(|x| format!("{}/Library/Audio/Plug-Ins/VST3/", x))
-            .ok(),
+        (PluginFormat::Vst3, OperatingSystem::Linux, InstallScope::System) => {
+            Some("/usr/lib/vst3/".to_string())
+        }
+        (PluginFormat::Vst3, _, _) if is_apple => apple_folder("VST3"),
 
-        (PluginFormat::Auv2, OperatingSystem::MacOSX(_))
-        | (PluginFormat::Auv2, OperatingSystem::Darwin(_)) => var("HOME")
-            .map(|x| format!("{}/Library/Audio/Plug-Ins/Components/", x))
-            .ok(),
+        (PluginFormat::Auv2, _, _) if is_apple => apple_folder("Components"),
 
         _ => None,
     };
@@ -217,3 +339,27 @@ pub fn zip_archive(path: &Path, archive: &Path) -> Result<()> {
 
     Ok(())
 }
+
+/// Looks up an executable in `PATH`, the way a shell would.
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|x| x.join(&name))
+        .find(|x| x.is_file())
+}
+
+/// Little endian writers for the binaries the ELF, PE and Mach-O tests build.
+#[cfg(test)]
+pub mod fixture {
+    pub fn put_u16(data: &mut [u8], offset: usize, value: u16) {
+        data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u32(data: &mut [u8], offset: usize, value: u32) {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u64(data: &mut [u8], offset: usize, value: u64) {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+}
@@ -0,0 +1,140 @@
+use super::PluginFormat;
+use crate::cli::{Command, Error, Result, report_message, report_span};
+use owo_colors::OwoColorize;
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Validator {
+    Pluginval,
+    ClapValidator,
+}
+
+impl Validator {
+    pub fn supports(&self, format: PluginFormat) -> bool {
+        match self {
+            Validator::Pluginval => matches!(format, PluginFormat::Vst3 | PluginFormat::Auv2),
+            Validator::ClapValidator => format == PluginFormat::Clap,
+        }
+    }
+
+    fn url(&self) -> &'static str {
+        match self {
+            Validator::Pluginval => "https://github.com/Tracktion/pluginval",
+            Validator::ClapValidator => "https://github.com/free-audio/clap-validator",
+        }
+    }
+}
+
+impl Display for Validator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Validator::Pluginval => "pluginval",
+            Validator::ClapValidator => "clap-validator",
+        })
+    }
+}
+
+impl FromStr for Validator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pluginval" => Ok(Validator::Pluginval),
+            "clap-validator" => Ok(Validator::ClapValidator),
+            _ => Err(
+                Error::new(format!("unknown validator {}", s.bold())).with_note(format!(
+                    "use either {} or {}",
+                    "pluginval".bold().bright_cyan(),
+                    "clap-validator".bold().bright_green()
+                )),
+            ),
+        }
+    }
+}
+
+/// Resolves the validator executable, preferring an explicitly configured path over `PATH`.
+pub fn find_validator(validator: Validator, path: Option<&Path>) -> Result<PathBuf> {
+    if let Some(path) = path {
+        if !path.is_file() {
+            return Err(Error::new(format!(
+                "{} was not found at {}",
+                validator.bold(),
+                path.display().bold()
+            )));
+        }
+
+        return Ok(path.to_path_buf());
+    }
+
+    super::find_program(&validator.to_string()).ok_or_else(|| {
+        Error::new(format!("{} was not found on PATH", validator.bold()))
+            .with_note(format!(
+                "you can install {} from {}",
+                validator.bold(),
+                validator.url()
+            ))
+            .with_note(format!(
+                "or point to it with {} in {}",
+                format!("tools.{}", validator).bold(),
+                crate::config::PROJECT_FILE
+            ))
+    })
+}
+
+/// Runs a validator against a single bundle and writes everything it prints to `report`.
+/// `strictness` is only understood by pluginval.
+pub fn run_validator(
+    validator: Validator,
+    program: &Path,
+    strictness: Option<u32>,
+    bundle: &Path,
+    report: &Path,
+) -> Result<()> {
+    report_span!(
+        "running {} on {}",
+        validator.bold(),
+        bundle.display().bold()
+    );
+
+    let report_dir = report.parent().unwrap();
+    std::fs::create_dir_all(report_dir)?;
+    let report_file = RefCell::new(std::fs::File::create(report)?);
+
+    let command = Command::new(&program.to_string_lossy());
+    let command = match (validator, strictness) {
+        (Validator::Pluginval, Some(strictness)) => command
+            .arg("--strictness-level")
+            .arg(strictness.to_string()),
+        _ => command,
+    };
+
+    let command = match validator {
+        Validator::Pluginval => command
+            .arg("--validate-in-process")
+            .arg("--output-dir")
+            .arg(report_dir)
+            .arg(bundle),
+        Validator::ClapValidator => command.arg("validate").arg(bundle),
+    };
+
+    let log = |line: &str| {
+        report_message!("{}", line.trim_end());
+        let _ = report_file.borrow_mut().write_all(line.as_bytes());
+    };
+
+    let result = command.run_stdout_stderr(log, log);
+    result.map_err(|e| {
+        e.with_message(format!(
+            "{} failed for {}",
+            validator.bold(),
+            bundle.display().bold()
+        ))
+        .with_note(format!("the full report is in {}", report.display()))
+    })
+}
@@ -0,0 +1,93 @@
+use crate::cli::{Error, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::{
+    path::PathBuf,
+    sync::mpsc::{Receiver, RecvTimeoutError, channel},
+    time::{Duration, Instant},
+};
+
+/// Watches directories recursively and collects bursts of changes into a single notification.
+pub struct FileWatcher {
+    receiver: Receiver<PathBuf>,
+    ignore: Vec<PathBuf>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl FileWatcher {
+    /// Changes below any of the `ignore` paths, like the target directory, are dropped.
+    pub fn new(dirs: &[PathBuf], ignore: Vec<PathBuf>) -> Result<Self> {
+        let (sender, receiver) = channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+
+                if !matches!(event.kind, EventKind::Access(_)) {
+                    for path in event.paths {
+                        let _ = sender.send(path);
+                    }
+                }
+            })
+            .map_err(|e| Error::new("failed to start watching for file changes").with_note(e))?;
+
+        for dir in dirs {
+            watcher.watch(dir, RecursiveMode::Recursive).map_err(|e| {
+                Error::new(format!("failed to watch {}", dir.display())).with_note(e)
+            })?;
+        }
+
+        Ok(Self {
+            receiver,
+            ignore,
+            _watcher: watcher,
+        })
+    }
+
+    /// Blocks until a file changes and no further changes happened for `debounce`. Changes made
+    /// while the caller was busy are returned immediately, so they're never lost. Changes to
+    /// ignored paths don't extend the wait.
+    pub fn wait(&self, debounce: Duration) -> Result<Vec<PathBuf>> {
+        let mut changes = Vec::new();
+        while changes.is_empty() {
+            let path = self
+                .receiver
+                .recv()
+                .map_err(|_| Error::new("stopped watching for file changes"))?;
+
+            self.push_change(&mut changes, path);
+        }
+
+        let mut deadline = Instant::now() + debounce;
+        loop {
+            match self
+                .receiver
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(path) => {
+                    if self.push_change(&mut changes, path) {
+                        deadline = Instant::now() + debounce;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::new("stopped watching for file changes"));
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Returns whether the path is watched, even if it already changed before.
+    fn push_change(&self, changes: &mut Vec<PathBuf>, path: PathBuf) -> bool {
+        let ignored = self.ignore.iter().any(|x| path.starts_with(x))
+            || path.components().any(|x| x.as_os_str() == ".git");
+
+        if !ignored && !changes.contains(&path) {
+            changes.push(path);
+        }
+
+        !ignored
+    }
+}
@@ -1,5 +1,10 @@
-use crate::cli::{Command, Error, Result};
+use crate::cli::{Command, Error, Result, continue_log};
 use owo_colors::OwoColorize;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
 use target_lexicon::{OperatingSystem, Triple};
 
 pub fn zig_triple(triple: &Triple, glibc: Option<&str>) -> Result<String> {
@@ -56,10 +61,15 @@ pub fn zig_triple(triple: &Triple, glibc: Option<&str>) -> Result<String> {
 }
 
 pub fn ensure_zig_installed() -> Result<()> {
+    zig_version().map(|_| ())
+}
+
+/// The version of the installed zig, fails if it's missing or older than 0.14.
+pub fn zig_version() -> Result<String> {
     let zig_version = Command::new("zig").arg("version").run().unwrap_or_default();
+    let zig_version = zig_version.trim();
 
     let minor = zig_version
-        .trim()
         .split(".")
         .nth(1)
         .unwrap_or_default()
@@ -67,15 +77,219 @@ pub fn ensure_zig_installed() -> Result<()> {
         .unwrap_or_default();
 
     if minor < 14 {
-        return Err(Error::new(format!(
+        let mut error = Error::new(format!(
             "cross compilation requires {} (>= 0.14.0) to be installed",
             "zig".bold()
         ))
         .with_note(format!(
             "you can install {} from https://ziglang.org",
             "zig".bold()
-        )));
+        ));
+
+        if !zig_version.is_empty() {
+            error = error.with_note(format!("found zig {}", zig_version));
+        }
+
+        return Err(error);
+    }
+
+    Ok(zig_version.to_string())
+}
+
+/// Writes a script that cargo can use as the linker of a target, it runs `picobundler zig-cc`
+/// for `zig_triple` as cargo only accepts a single program.
+pub fn zig_linker(target_dir: &Path, zig_triple: &str) -> Result<PathBuf> {
+    // targets built at the same time can share a zig triple
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let exe = std::env::current_exe()?;
+    let dir = target_dir.join("zig-linker");
+    std::fs::create_dir_all(&dir)?;
+
+    #[cfg(windows)]
+    let (path, script) = (
+        dir.join(format!("{}.cmd", zig_triple)),
+        format!(
+            "@echo off\r\n\"{}\" zig-cc {} %*\r\n",
+            exe.display(),
+            zig_triple
+        ),
+    );
+
+    #[cfg(not(windows))]
+    let (path, script) = (
+        dir.join(format!("{}.sh", zig_triple)),
+        format!(
+            "#!/bin/sh\nexec '{}' zig-cc {} \"$@\"\n",
+            exe.display().to_string().replace('\'', "'\\''"),
+            zig_triple
+        ),
+    );
+
+    // a running build may be executing the script, only replace it when it changed
+    if std::fs::read_to_string(&path).ok().as_deref() != Some(script.as_str()) {
+        std::fs::write(&path, script)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+        }
+    }
+
+    Ok(path)
+}
+
+/// Runs `zig cc` as the linker rustc expects, without the arguments that only apply to the
+/// system linkers.
+pub fn zig_cc(zig_triple: &str, args: Vec<OsString>) -> Result<()> {
+    continue_log();
+
+    // rustc shows the output of the linker itself, so it's passed on as is
+    Command::new("zig")
+        .arg("cc")
+        .arg("-target")
+        .arg(zig_triple)
+        .args(filter_linker_args(args)?)
+        .run_stdout_stderr(|line| print!("{}", line), |line| eprint!("{}", line))
+}
+
+fn filter_linker_args(args: Vec<OsString>) -> Result<Vec<OsString>> {
+    // zig has no libgcc_s for its targets, but it builds libunwind, which has the same
+    // `_Unwind_*` functions that panics need. when the search paths have a libgcc_s, e.g. the
+    // one of the system for a native build, it's still linked, so the binary keeps depending
+    // on the library it would depend on with the system linker.
+    let has_gcc_s = search_dirs(&args)?
+        .iter()
+        .any(|dir| dir.join("libgcc_s.so").exists() || dir.join("libgcc_s.so.1").exists());
+
+    filter_args(args, has_gcc_s)
+}
+
+fn filter_args(args: Vec<OsString>, has_gcc_s: bool) -> Result<Vec<OsString>> {
+    let mut filtered = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let Some(text) = arg.to_str() else {
+            filtered.push(arg);
+            continue;
+        };
+
+        match text {
+            "-lgcc_s" if !has_gcc_s => filtered.push("-lunwind".into()),
+            "-lgcc" | "-lgcc_eh" | "-l:libpthread.a" => {}
+            // the target is already set with -target
+            "-arch" => {
+                args.next();
+            }
+            "-Wl,-dylib" | "-Wl,--disable-auto-image-base" => {}
+            // zig always links with its own lld
+            _ if text.starts_with("-fuse-ld=") || text.starts_with("-B") => {}
+            _ if text.starts_with("--target=") => {}
+            // rustc passes the arguments in a file when the command line gets too long
+            _ if text.starts_with('@') => {
+                let file = &text[1..];
+                let mut content = String::new();
+                for arg in filter_args(read_response_file(file)?, has_gcc_s)? {
+                    content.push_str(&escape_response_arg(&arg.to_string_lossy()));
+                    content.push('\n');
+                }
+
+                let file = format!("{}.zig", file);
+                std::fs::write(&file, content)?;
+                filtered.push(format!("@{}", file).into());
+            }
+            _ => filtered.push(arg),
+        }
+    }
+
+    Ok(filtered)
+}
+
+/// Collects the library search paths passed with `-L`, including the ones in response files.
+fn search_dirs(args: &[OsString]) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let Some(text) = arg.to_str() else {
+            continue;
+        };
+
+        if text == "-L" {
+            dirs.extend(args.next().map(PathBuf::from));
+        } else if let Some(dir) = text.strip_prefix("-L") {
+            dirs.push(PathBuf::from(dir));
+        } else if let Some(file) = text.strip_prefix('@') {
+            dirs.extend(search_dirs(&read_response_file(file)?)?);
+        }
+    }
+
+    Ok(dirs)
+}
+
+fn read_response_file(file: &str) -> Result<Vec<OsString>> {
+    Ok(std::fs::read_to_string(file)?
+        .lines()
+        .map(|x| unescape_response_arg(x).into())
+        .collect())
+}
+
+/// Response files of rustc have one argument per line with backslashes and spaces escaped.
+fn unescape_response_arg(line: &str) -> String {
+    let mut arg = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => arg.extend(chars.next()),
+            c => arg.push(c),
+        }
     }
 
-    Ok(())
+    arg
+}
+
+fn escape_response_arg(arg: &str) -> String {
+    let mut escaped = String::new();
+    for c in arg.chars() {
+        if matches!(c, '\\' | ' ' | '"' | '\'') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_gcc_s() {
+        let dir = std::env::temp_dir().join(format!("picobundler-zig-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let args = vec![
+            "-L".into(),
+            dir.clone().into(),
+            "-lgcc_s".into(),
+            "-lgcc".into(),
+        ];
+        let expected =
+            |lib: &str| -> Vec<OsString> { vec!["-L".into(), dir.clone().into(), lib.into()] };
+
+        assert_eq!(
+            filter_linker_args(args.clone()).unwrap(),
+            expected("-lunwind")
+        );
+
+        std::fs::write(dir.join("libgcc_s.so.1"), "").unwrap();
+        assert_eq!(filter_linker_args(args).unwrap(), expected("-lgcc_s"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
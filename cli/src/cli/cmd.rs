@@ -1,3 +1,4 @@
+use super::{interrupt::register_child, log::write_log};
 use crate::cli::{Error, Result};
 use owo_colors::OwoColorize;
 use std::{
@@ -17,8 +18,14 @@ pub struct Command {
 
 impl Command {
     pub fn new(program: &str) -> Self {
+        let mut inner = std::process::Command::new(program);
+
+        // lets the interrupt handler kill everything the child spawned as well
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut inner, 0);
+
         Self {
-            inner: std::process::Command::new(program),
+            inner,
             print: vec![Component::Cmd(program.to_string())],
         }
     }
@@ -86,16 +93,21 @@ impl Command {
 
     pub fn run(mut self) -> Result<String> {
         let program = format!("{}", self);
-        let output = self
+        write_log(&format!("$ {:?}", self));
+        let child = self
             .inner
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .map_err(|e| format_error(&program, e, None))?
+            .map_err(|e| format_error(&program, e, None))?;
+
+        let _guard = register_child(child.id());
+        let output = child
             .wait_with_output()
             .map_err(|e| format_error(&program, e, None))?;
 
+        log_status(output.status);
         if output.status.success() {
             Ok(String::from_utf8_lossy(&output.stdout).to_string())
         } else {
@@ -106,6 +118,7 @@ impl Command {
 
     pub fn run_stdout(mut self, stream: impl FnMut(&str)) -> Result<()> {
         let program = format!("{}", self);
+        write_log(&format!("$ {:?}", self));
         let mut result = self
             .inner
             .stdin(Stdio::null())
@@ -114,12 +127,14 @@ impl Command {
             .spawn()
             .map_err(|e| format_error(&program, e, None))?;
 
+        let _guard = register_child(result.id());
         read_to_end(result.stdout.take().unwrap(), stream)?;
 
         let result = result
             .wait_with_output()
             .map_err(|e| format_error(&program, e, None))?;
 
+        log_status(result.status);
         if !result.status.success() {
             let stderr = String::from_utf8_lossy(&result.stderr).to_string();
             return Err(format_error(&program, stderr, Some(result.status)));
@@ -134,6 +149,7 @@ impl Command {
         mut stderr: impl FnMut(&str),
     ) -> Result<()> {
         let program = format!("{}", self);
+        write_log(&format!("$ {:?}", self));
         let mut result = self
             .inner
             .stdin(Stdio::null())
@@ -142,6 +158,7 @@ impl Command {
             .spawn()
             .map_err(|e| format_error(&program, e, None))?;
 
+        let _guard = register_child(result.id());
         read_double_pipe(
             result.stdout.take().unwrap(),
             result.stderr.take().unwrap(),
@@ -152,6 +169,7 @@ impl Command {
         )?;
 
         let result = result.wait().map_err(|e| format_error(&program, e, None))?;
+        log_status(result);
         if !result.success() {
             return Err(format_error(
                 &program,
@@ -201,9 +219,12 @@ impl Debug for Command {
                 Component::Cmd(cmd) => {
                     write!(f, "{}", cmd)?;
                 }
-                Component::Arg(arg) | Component::ArgSecret(arg) => {
+                Component::Arg(arg) => {
                     write!(f, " {}", arg)?;
                 }
+                Component::ArgSecret(_) => {
+                    write!(f, " ***")?;
+                }
             }
         }
 
@@ -218,6 +239,13 @@ enum Component {
     ArgSecret(String),
 }
 
+fn log_status(status: std::process::ExitStatus) {
+    write_log(&match status.code() {
+        Some(code) => format!("exit code: {}", code),
+        None => "terminated by a signal".to_string(),
+    });
+}
+
 fn format_error(
     program: &str,
     stderr: impl Display,
@@ -3,8 +3,9 @@ use std::{
     fmt::Display,
     io::{self, Write},
 };
+use tinyjson::JsonValue;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Error(Box<ErrorImpl>);
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -33,6 +34,68 @@ impl Error {
         self.0.message = message.to_string();
         self
     }
+
+    pub fn message(&self) -> &str {
+        &self.0.message
+    }
+
+    pub fn notes(&self) -> &[String] {
+        &self.0.note
+    }
+
+    /// Prints the error with its trace and notes to stderr.
+    pub fn print(&self) {
+        let ErrorImpl {
+            message,
+            trace,
+            note,
+        } = &*self.0;
+
+        super::emit_json(
+            "error",
+            [
+                ("message", super::json_string(message)),
+                (
+                    "trace",
+                    JsonValue::Array(trace.iter().rev().map(super::json_string).collect()),
+                ),
+                (
+                    "notes",
+                    JsonValue::Array(note.iter().map(super::json_string).collect()),
+                ),
+            ],
+        );
+
+        super::log::write_log(&format!("error: {}", message));
+        for line in trace.iter().rev() {
+            super::log::write_log(&format!("  caused by: {}", line));
+        }
+        for note in note.iter() {
+            super::log::write_log(&format!("  note: {}", note));
+        }
+
+        eprintln!("{}: {}", "error".bright_red().bold(), message.bold());
+
+        if !trace.is_empty() {
+            eprintln!("  {} caused by:", "-->".bright_blue().bold());
+
+            for line in trace.iter().rev() {
+                eprintln!("   {}\t{}", "|".bright_blue().bold(), line);
+            }
+        }
+
+        for note in note.iter() {
+            eprintln!(
+                "   {} {}: {}",
+                "=".bright_blue().bold(),
+                "note".bold(),
+                note
+            );
+        }
+
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+    }
 }
 
 impl<T: std::fmt::Display> From<T> for Error {
@@ -50,40 +113,18 @@ pub fn print_error(cb: impl FnOnce() -> Result<()>) -> ! {
             std::process::exit(0);
         }
         Err(e) => {
-            let ErrorImpl {
-                message,
-                trace,
-                note,
-            } = *e.0;
-
-            eprintln!("{}: {}", "error".bright_red().bold(), message.bold());
-
-            if !trace.is_empty() {
-                eprintln!("  {} caused by:", "-->".bright_blue().bold());
-
-                for line in trace.iter().rev() {
-                    eprintln!("   {}\t{}", "|".bright_blue().bold(), line);
-                }
-            }
-
-            for note in note.iter() {
-                eprintln!(
-                    "   {} {}: {}",
-                    "=".bright_blue().bold(),
-                    "note".bold(),
-                    note
-                );
-            }
-
-            let _ = io::stdout().flush();
-            let _ = io::stderr().flush();
+            let e = match super::log::log_path() {
+                Some(path) => e.with_note(format!("the full log is in {}", path.display())),
+                None => e,
+            };
 
+            e.print();
             std::process::exit(1);
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 struct ErrorImpl {
     message: String,
     trace: Vec<String>,
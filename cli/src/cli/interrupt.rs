@@ -0,0 +1,102 @@
+use owo_colors::OwoColorize;
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Exit code used when picobundler is interrupted, the usual 128 + SIGINT.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+static CLEANUP: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Kills the registered children, removes the registered paths and exits on Ctrl-C.
+pub fn set_interrupt_handler() {
+    let _ = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        // holding stderr keeps other threads from printing the errors of the killed children
+        let _lock = std::io::stderr().lock();
+        super::progress::clear();
+
+        for pid in std::mem::take(&mut *CHILDREN.lock().unwrap()) {
+            kill_process(pid);
+        }
+
+        for path in std::mem::take(&mut *CLEANUP.lock().unwrap()) {
+            remove_path(&path);
+        }
+
+        super::log::write_log("interrupted");
+        eprintln!("{}", "interrupted".bright_yellow().bold());
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    });
+}
+
+/// Registers a spawned process, it's killed on Ctrl-C until the guard is dropped.
+pub fn register_child(pid: u32) -> impl Drop {
+    struct ChildGuard(u32);
+    impl Drop for ChildGuard {
+        fn drop(&mut self) {
+            CHILDREN.lock().unwrap().retain(|x| *x != self.0);
+        }
+    }
+
+    let mut children = CHILDREN.lock().unwrap();
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        // spawned while the handler was already killing everything else
+        kill_process(pid);
+    } else {
+        children.push(pid);
+    }
+
+    ChildGuard(pid)
+}
+
+/// Registers a path that is only complete once the guard is dropped, it's removed on Ctrl-C.
+pub fn remove_on_interrupt(path: &Path) -> impl Drop {
+    struct CleanupGuard(PathBuf);
+    impl Drop for CleanupGuard {
+        fn drop(&mut self) {
+            CLEANUP.lock().unwrap().retain(|x| *x != self.0);
+        }
+    }
+
+    CLEANUP.lock().unwrap().push(path.to_path_buf());
+    CleanupGuard(path.to_path_buf())
+}
+
+fn kill_process(pid: u32) {
+    // children are the leaders of their own process group, see `Command::new`
+    #[cfg(unix)]
+    let command = std::process::Command::new("kill")
+        .args(["-TERM", "--"])
+        .arg(format!("-{}", pid))
+        .output();
+
+    #[cfg(windows)]
+    let command = std::process::Command::new("taskkill")
+        .args(["/F", "/T", "/PID"])
+        .arg(pid.to_string())
+        .output();
+
+    let _ = command;
+}
+
+fn remove_path(path: &Path) {
+    match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => {
+            let _ = std::fs::remove_dir_all(path);
+        }
+        Ok(_) => {
+            let _ = std::fs::remove_file(path);
+        }
+        Err(_) => {}
+    }
+}
@@ -0,0 +1,70 @@
+use super::{Error, Result};
+use owo_colors::OwoColorize;
+use std::{
+    collections::HashMap,
+    io::Write,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tinyjson::JsonValue;
+
+static JSON_MESSAGES: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Progress and errors on stderr only
+    #[default]
+    Human,
+    /// Additionally one JSON event per line on stdout
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(
+                Error::new(format!("unknown message format {}", s.bold())).with_note(format!(
+                    "use either {} or {}",
+                    "human".bold().bright_cyan(),
+                    "json".bold().bright_green()
+                )),
+            ),
+        }
+    }
+}
+
+pub fn set_message_format(format: MessageFormat) {
+    JSON_MESSAGES.store(format == MessageFormat::Json, Ordering::Relaxed);
+}
+
+pub fn json_messages() -> bool {
+    JSON_MESSAGES.load(Ordering::Relaxed)
+}
+
+/// Writes `{"event": event, ...fields}` as a single line to stdout if JSON messages are enabled.
+pub fn emit_json(event: &str, fields: impl IntoIterator<Item = (&'static str, JsonValue)>) {
+    if !json_messages() {
+        return;
+    }
+
+    let mut object = fields
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect::<HashMap<_, _>>();
+    object.insert("event".to_string(), JsonValue::String(event.to_string()));
+
+    if let Ok(line) = JsonValue::Object(object).stringify() {
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{}", line);
+        let _ = stdout.flush();
+    }
+}
+
+/// A JSON string without the terminal colors of `text`.
+pub fn json_string(text: impl AsRef<str>) -> JsonValue {
+    JsonValue::String(console::strip_ansi_codes(text.as_ref()).to_string())
+}
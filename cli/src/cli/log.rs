@@ -0,0 +1,79 @@
+use super::Result;
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+struct LogFile {
+    file: File,
+    path: PathBuf,
+    start: Instant,
+}
+
+static LOG: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// Passed to the processes cargo starts, so `picobundler zig-cc` can write to the same log.
+pub const LOG_ENV: &str = "PICOBUNDLER_LOG";
+
+/// Starts writing every reported line, command and error to `<dir>/<timestamp>.log`, no matter
+/// whether `--verbose` is set.
+pub fn open_log(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    let path = dir.join(format!("{}.log", super::timings::timestamp()));
+    let file = File::create(&path)?;
+
+    *LOG.lock().unwrap() = Some(LogFile {
+        file,
+        path,
+        start: Instant::now(),
+    });
+
+    Ok(())
+}
+
+/// Appends to the log of the build that started this process, if there is one.
+pub fn continue_log() {
+    let Some(path) = std::env::var_os(LOG_ENV).map(PathBuf::from) else {
+        return;
+    };
+
+    if let Ok(file) = OpenOptions::new().append(true).open(&path) {
+        *LOG.lock().unwrap() = Some(LogFile {
+            file,
+            path,
+            start: Instant::now(),
+        });
+    }
+}
+
+pub fn log_path() -> Option<PathBuf> {
+    LOG.lock().unwrap().as_ref().map(|x| x.path.clone())
+}
+
+/// Appends `text` without colors, each line prefixed with the time since the log was opened and
+/// the thread, as parallel builds interleave.
+pub fn write_log(text: &str) {
+    if text.is_empty() {
+        return;
+    }
+
+    let mut log = LOG.lock().unwrap();
+    let Some(log) = log.as_mut() else {
+        return;
+    };
+
+    let prefix = format!(
+        "[{:>9.3}s {:>3}]",
+        log.start.elapsed().as_secs_f64(),
+        super::trace::thread_number(std::thread::current().id())
+    );
+
+    let text = console::strip_ansi_codes(text);
+    for line in text.lines() {
+        let _ = writeln!(log.file, "{} {}", prefix, line.trim_end());
+    }
+}
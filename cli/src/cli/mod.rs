@@ -1,9 +1,17 @@
 mod cmd;
 mod error;
+mod interrupt;
+mod json;
+mod log;
 mod progress;
+mod timings;
 mod trace;
 
 pub(crate) use cmd::Command;
 pub(crate) use error::{Error, Result, print_error};
-pub(crate) use progress::set_force_log;
-pub(crate) use trace::{StatusReporter, report_message, report_span};
+pub(crate) use interrupt::{remove_on_interrupt, set_interrupt_handler};
+pub(crate) use json::{MessageFormat, emit_json, json_messages, json_string, set_message_format};
+pub(crate) use log::{LOG_ENV, continue_log, log_path, open_log, write_log};
+pub(crate) use progress::{print_line, set_force_log};
+pub(crate) use timings::write_timings;
+pub(crate) use trace::{StatusReporter, report_message, report_span, report_warning};
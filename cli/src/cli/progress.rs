@@ -18,12 +18,33 @@ pub fn set_force_log(force: bool) {
     FORCE_LOG.store(force, Ordering::Relaxed);
 }
 
+/// Removes the status bar, it's drawn again on the next event.
+pub fn clear() {
+    draw_string("", String::new, String::new);
+}
+
 pub fn report(event: Event) {
     ensure_update_thread();
-    draw_string(generate_status_bar, || generate_event(event));
+
+    let text = generate_event(event);
+    super::log::write_log(&text);
+    draw_string("", generate_status_bar, || text);
+}
+
+/// Prints `text` above the status bar, where it stays instead of being redrawn over.
+pub fn print_line(text: &str) {
+    ensure_update_thread();
+
+    super::log::write_log(text);
+    draw_string(text, generate_status_bar, || text.to_string());
 }
 
-fn draw_string(supported: impl FnOnce() -> String, unsupported: impl FnOnce() -> String) {
+/// Redraws the status bar, with `persistent` printed above it and kept on the terminal.
+fn draw_string(
+    persistent: &str,
+    supported: impl FnOnce() -> String,
+    unsupported: impl FnOnce() -> String,
+) {
     static LAST_LINES: AtomicU32 = AtomicU32::new(0);
 
     let _lock = std::io::stderr().lock();
@@ -46,6 +67,9 @@ fn draw_string(supported: impl FnOnce() -> String, unsupported: impl FnOnce() ->
     let line_count = string.lines().count();
 
     let _ = stderr.clear_last_lines(LAST_LINES.swap(line_count as u32, Ordering::Relaxed) as usize);
+    for line in persistent.lines() {
+        let _ = stderr.write_line(line);
+    }
     for line in string.lines() {
         let _ = stderr.write_str(&truncate_str(line, width as usize, "..."));
         let _ = stderr.write_line("\x1b[0m");
@@ -0,0 +1,163 @@
+use super::{Result, StatusReporter, trace::SpanTiming};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tinyjson::JsonValue;
+
+/// How many steps the summary lists.
+const SUMMARY_STEPS: usize = 25;
+
+/// Writes the spans finished since the last call as a Chrome trace (`chrome://tracing` or
+/// Perfetto) and a plain text summary of the slowest steps into `dir`. Returns the summary path.
+pub fn write_timings(dir: &Path) -> Result<PathBuf> {
+    let timings = StatusReporter::get().take_timings();
+    let name = timestamp();
+
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(
+        dir.join(format!("{}.json", name)),
+        chrome_trace(&timings).stringify()?,
+    )?;
+
+    let summary = dir.join(format!("{}.txt", name));
+    std::fs::write(&summary, summary_text(&timings))?;
+    Ok(summary)
+}
+
+/// The current UTC time as `YYYYMMDD-HHMMSS`, sorts chronologically and works as a file name.
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    // days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+fn chrome_trace(timings: &[SpanTiming]) -> JsonValue {
+    let micros = |x: Duration| JsonValue::Number(x.as_secs_f64() * 1e6);
+
+    let events = timings
+        .iter()
+        .map(|timing| {
+            let fields = [
+                ("name", JsonValue::String(plain(timing.path.last()))),
+                ("cat", JsonValue::String("picobundler".to_string())),
+                ("ph", JsonValue::String("X".to_string())),
+                ("ts", micros(timing.start)),
+                ("dur", micros(timing.duration)),
+                ("pid", JsonValue::Number(1.0)),
+                ("tid", JsonValue::Number(timing.thread as f64)),
+            ];
+
+            JsonValue::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    JsonValue::Object(HashMap::from([
+        ("traceEvents".to_string(), JsonValue::Array(events)),
+        (
+            "displayTimeUnit".to_string(),
+            JsonValue::String("ms".to_string()),
+        ),
+    ]))
+}
+
+fn summary_text(timings: &[SpanTiming]) -> String {
+    let total = timings
+        .iter()
+        .map(|x| x.start + x.duration)
+        .max()
+        .unwrap_or_default()
+        .saturating_sub(timings.iter().map(|x| x.start).min().unwrap_or_default());
+
+    // time spent in a step itself, without its nested steps and the parallel work it waited for
+    let mut steps = timings
+        .iter()
+        .map(|timing| {
+            let end = timing.start + timing.duration;
+            let mut nested = timings
+                .iter()
+                .filter(|x| {
+                    let is_child = x.thread == timing.thread
+                        && x.path.len() == timing.path.len() + 1
+                        && x.path.starts_with(&timing.path);
+                    let is_worker = x.thread != timing.thread && x.path.len() == 1;
+
+                    (is_child || is_worker)
+                        && x.start >= timing.start
+                        && x.start + x.duration <= end
+                })
+                .map(|x| (x.start, x.start + x.duration))
+                .collect::<Vec<_>>();
+            nested.sort();
+
+            // parallel steps overlap, count the time covered by any of them once
+            let mut covered = Duration::ZERO;
+            let mut covered_until = timing.start;
+            for (start, end) in nested {
+                covered += end.saturating_sub(start.max(covered_until));
+                covered_until = covered_until.max(end);
+            }
+
+            (timing, timing.duration.saturating_sub(covered))
+        })
+        .collect::<Vec<_>>();
+    steps.sort_by_key(|x| std::cmp::Reverse(x.1));
+
+    let mut text = String::new();
+    let _ = writeln!(text, "total time: {:.2}s", total.as_secs_f64());
+    let _ = writeln!(text);
+    let _ = writeln!(text, "{:>9} {:>9}  step", "self", "total");
+
+    for (timing, self_time) in steps.into_iter().take(SUMMARY_STEPS) {
+        let _ = writeln!(
+            text,
+            "{:>8.2}s {:>8.2}s  {}",
+            self_time.as_secs_f64(),
+            timing.duration.as_secs_f64(),
+            plain(timing.path.last())
+        );
+
+        if timing.path.len() > 1 {
+            let parents = timing.path[..timing.path.len() - 1]
+                .iter()
+                .map(|x| plain(Some(x)))
+                .collect::<Vec<_>>();
+            let _ = writeln!(text, "{:>21}  in {}", "", parents.join(" > "));
+        }
+    }
+
+    text
+}
+
+fn plain(span: Option<&String>) -> String {
+    console::strip_ansi_codes(span.map_or("", |x| x.as_str())).to_string()
+}
@@ -1,7 +1,13 @@
-use super::progress::{Event, report};
+use super::json::{emit_json, json_string};
+use super::progress::{Event, print_line, report};
+use owo_colors::OwoColorize;
 use std::{
-    sync::{Mutex, OnceLock},
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
     thread::ThreadId,
+    time::{Duration, Instant},
 };
 
 macro_rules! report_span {
@@ -16,16 +22,36 @@ macro_rules! report_message {
     };
 }
 
-pub(crate) use {report_message, report_span};
+macro_rules! report_warning {
+    ($($arg:tt)*) => {
+        $crate::cli::StatusReporter::get().report_warning(format!($($arg)*));
+    };
+}
+
+pub(crate) use {report_message, report_span, report_warning};
 
 pub struct StatusTrace {
     pub span: String,
     pub message: String,
+    pub start: Instant,
+}
+
+/// A finished span, recorded for `--timings`.
+pub struct SpanTiming {
+    /// The spans enclosing this one on the same thread, outermost first, ending with this one
+    pub path: Vec<String>,
+    pub thread: u64,
+    /// Since the reporter was created
+    pub start: Duration,
+    pub duration: Duration,
 }
 
 #[doc(hidden)]
 pub struct StatusReporter {
     stacks: Mutex<Vec<(ThreadId, Vec<StatusTrace>)>>,
+    epoch: Instant,
+    record_timings: AtomicBool,
+    timings: Mutex<Vec<SpanTiming>>,
 }
 
 impl StatusReporter {
@@ -33,9 +59,21 @@ impl StatusReporter {
         static INSTANCE: OnceLock<StatusReporter> = OnceLock::new();
         INSTANCE.get_or_init(|| Self {
             stacks: Mutex::new(Vec::new()),
+            epoch: Instant::now(),
+            record_timings: AtomicBool::new(false),
+            timings: Mutex::new(Vec::new()),
         })
     }
 
+    /// Keeps the timing of every finished span until [`Self::take_timings`] is called.
+    pub fn record_timings(&self) {
+        self.record_timings.store(true, Ordering::Relaxed);
+    }
+
+    pub fn take_timings(&self) -> Vec<SpanTiming> {
+        std::mem::take(&mut *self.timings.lock().unwrap())
+    }
+
     pub fn request_span(&self, span: String) -> impl Drop {
         struct EndStatus(ThreadId);
         impl Drop for EndStatus {
@@ -61,10 +99,21 @@ impl StatusReporter {
         report(Event::Message(message));
     }
 
+    /// Unlike messages, warnings are printed above the status bar and stay there.
+    pub fn report_warning(&self, message: String) {
+        emit_json("warning", [("message", json_string(&message))]);
+        print_line(&format!(
+            "{}: {}",
+            "warning".bright_yellow().bold(),
+            message
+        ));
+    }
+
     pub fn report_start(&self, thread: ThreadId, span: String) {
         let trace = StatusTrace {
             span: span.clone(),
             message: span.clone(),
+            start: Instant::now(),
         };
 
         let mut stacks = self.stacks.lock().unwrap();
@@ -74,28 +123,62 @@ impl StatusReporter {
         }
 
         drop(stacks);
+        emit_json(
+            "span-begin",
+            [
+                ("span", json_string(&span)),
+                ("thread", thread_json(thread)),
+            ],
+        );
         report(Event::Begin(span));
     }
 
     pub fn report_end(&self, thread: ThreadId) -> bool {
         let mut stacks = self.stacks.lock().unwrap();
-        match stacks.iter_mut().find(|x| x.0 == thread) {
+        let trace = match stacks.iter_mut().find(|x| x.0 == thread) {
             Some((_, stack)) => {
-                stack.pop();
+                if self.record_timings.load(Ordering::Relaxed) {
+                    self.record_timing(thread, stack);
+                }
+
+                let trace = stack.pop();
                 if stack.is_empty() {
                     stacks.retain(|x| x.0 != thread);
                 }
+                trace
             }
             None => {
                 return false;
             }
-        }
+        };
 
         drop(stacks);
+        if let Some(trace) = trace {
+            emit_json(
+                "span-end",
+                [
+                    ("span", json_string(&trace.span)),
+                    ("thread", thread_json(thread)),
+                ],
+            );
+        }
         report(Event::End);
         true
     }
 
+    fn record_timing(&self, thread: ThreadId, stack: &[StatusTrace]) {
+        let Some(trace) = stack.last() else {
+            return;
+        };
+
+        self.timings.lock().unwrap().push(SpanTiming {
+            path: stack.iter().map(|x| x.span.clone()).collect(),
+            thread: thread_number(thread),
+            start: trace.start.duration_since(self.epoch),
+            duration: trace.start.elapsed(),
+        });
+    }
+
     pub fn request_trace<T>(
         &self,
         thread: ThreadId,
@@ -116,3 +199,18 @@ impl StatusReporter {
         }
     }
 }
+
+/// Spans of parallel builds interleave, the thread tells which begin an end belongs to.
+fn thread_json(thread: ThreadId) -> tinyjson::JsonValue {
+    tinyjson::JsonValue::Number(thread_number(thread) as f64)
+}
+
+/// `ThreadId::as_u64` is unstable, but its debug output is `ThreadId(<number>)`.
+pub(super) fn thread_number(thread: ThreadId) -> u64 {
+    format!("{:?}", thread)
+        .chars()
+        .filter(|x| x.is_ascii_digit())
+        .collect::<String>()
+        .parse()
+        .unwrap_or_default()
+}
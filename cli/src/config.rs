@@ -0,0 +1,630 @@
+use crate::build::{
+    BuildTarget, CmakeBuildType, InstallMode, PluginFormat, UnknownLibraries, Validator, Vst3Sdk,
+    cargo_metadata,
+};
+use crate::cli::{Error, Result};
+use owo_colors::OwoColorize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tinyjson::JsonValue;
+
+pub const PROJECT_FILE: &str = "picobundler.toml";
+
+const PROJECT_KEYS: &[&str] = &["preset", "tools"];
+const TOOLS_KEYS: &[&str] = &["pluginval", "clap-validator"];
+const PRESET_KEYS: &[&str] = &[
+    "packages",
+    "formats",
+    "vst3-sdk",
+    "targets",
+    "profile",
+    "features",
+    "all-features",
+    "no-default-features",
+    "install-targets",
+    "install",
+    "install-dir",
+    "install-dir-clap",
+    "install-dir-vst3",
+    "install-dir-auv2",
+    "install-subfolder",
+    "system-wide",
+    "validate",
+    "validator",
+    "validator-strictness",
+    "output-dir",
+    "sign",
+];
+const SIGN_KEYS: &[&str] = &["identity", "team", "username", "password-env"];
+const WORKSPACE_KEYS: &[&str] = &["local-cmake-path", "cmake-build-type"];
+const PACKAGE_KEYS: &[&str] = &[
+    "formats",
+    "vst3-sdk",
+    "name",
+    "bundle-id",
+    "vendor",
+    "targets",
+    "features",
+    "min-glibc",
+    "allowed-libraries",
+    "unknown-libraries",
+];
+
+/// `[workspace.metadata.picobundler]`
+#[derive(Debug, Clone, Default)]
+pub struct WorkspaceConfig {
+    pub local_cmake_path: Option<PathBuf>,
+    pub cmake_build_type: HashMap<String, CmakeBuildType>,
+}
+
+/// `[package.metadata.picobundler]`
+#[derive(Debug, Clone, Default)]
+pub struct PackageConfig {
+    pub formats: Option<Vec<PluginFormat>>,
+    pub vst3_sdk: Option<Vst3Sdk>,
+
+    pub name: Option<String>,
+    pub bundle_id: Option<String>,
+    pub vendor: Option<String>,
+
+    pub targets: Option<Vec<BuildTarget>>,
+    pub features: Option<Vec<String>>,
+    pub min_glibc: Option<String>,
+    pub allowed_libraries: Vec<String>,
+    pub unknown_libraries: Option<UnknownLibraries>,
+}
+
+/// `[preset.<name>]` in `picobundler.toml`
+#[derive(Debug, Clone, Default)]
+pub struct Preset {
+    pub packages: Option<Vec<String>>,
+    pub formats: Option<Vec<PluginFormat>>,
+    pub vst3_sdk: Option<Vst3Sdk>,
+    pub targets: Option<Vec<BuildTarget>>,
+    pub profile: Option<String>,
+
+    pub features: Option<Vec<String>>,
+    pub all_features: Option<bool>,
+    pub no_default_features: Option<bool>,
+    pub install_targets: Option<bool>,
+
+    pub install: Option<InstallMode>,
+    pub install_dir: Option<PathBuf>,
+    pub install_dir_clap: Option<PathBuf>,
+    pub install_dir_vst3: Option<PathBuf>,
+    pub install_dir_auv2: Option<PathBuf>,
+    pub install_subfolder: Option<String>,
+    pub system_wide: Option<bool>,
+    pub validate: Option<bool>,
+    pub validator: Option<Vec<Validator>>,
+    /// pluginval's `--strictness-level`, pluginval picks its own default without one
+    pub validator_strictness: Option<u32>,
+    pub output_dir: Option<PathBuf>,
+    pub sign: Option<SignConfig>,
+}
+
+/// `[preset.<name>.sign]` in `picobundler.toml`
+#[derive(Debug, Clone, Default)]
+pub struct SignConfig {
+    pub identity: String,
+    pub team: String,
+    pub username: String,
+    pub password_env: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub project_file: PathBuf,
+    pub presets: HashMap<String, Preset>,
+    /// `[tools]` in `picobundler.toml`, paths to external programs by name
+    pub tools: HashMap<String, PathBuf>,
+    pub workspace: WorkspaceConfig,
+    pub packages: HashMap<String, PackageConfig>,
+}
+
+impl Config {
+    pub fn load(workspace_dir: &Path) -> Result<Self> {
+        let project_file = workspace_dir.join(PROJECT_FILE);
+        let mut config = if project_file.exists() {
+            parse_project(&project_file, workspace_dir)
+                .map_err(|e| e.with_note(format!("defined in {}", project_file.display())))?
+        } else {
+            Config::default()
+        };
+
+        let metadata = cargo_metadata()?;
+
+        let workspace_root = metadata
+            .get("workspace_root")
+            .and_then(|x| x.get::<String>())
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                Error::new(format!("malformed output from {}", "cargo metadata".bold()))
+            })?;
+
+        let workspace = match picobundler_table(metadata.get("metadata")) {
+            Some(value) => {
+                parse_workspace("workspace.metadata.picobundler", value, &workspace_root).map_err(
+                    |e| {
+                        e.with_note(format!(
+                            "defined in {}",
+                            workspace_root.join("Cargo.toml").display()
+                        ))
+                    },
+                )?
+            }
+            None => WorkspaceConfig::default(),
+        };
+
+        let members = metadata
+            .get("workspace_members")
+            .and_then(|x| x.get::<Vec<JsonValue>>())
+            .map(|x| {
+                x.iter()
+                    .filter_map(|x| x.get::<String>().cloned())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let mut packages = HashMap::new();
+        for package in metadata
+            .get("packages")
+            .and_then(|x| x.get::<Vec<JsonValue>>())
+            .into_iter()
+            .flatten()
+            .filter_map(|x| x.get::<HashMap<String, JsonValue>>())
+        {
+            let field = |key: &str| {
+                package
+                    .get(key)
+                    .and_then(|x| x.get::<String>())
+                    .cloned()
+                    .unwrap_or_default()
+            };
+
+            if !members.contains(&field("id")) {
+                continue;
+            }
+
+            if let Some(value) = picobundler_table(package.get("metadata")) {
+                let config = parse_package("package.metadata.picobundler", value)
+                    .map_err(|e| e.with_note(format!("defined in {}", field("manifest_path"))))?;
+
+                packages.insert(field("name"), config);
+            }
+        }
+
+        config.project_file = project_file;
+        config.workspace = workspace;
+        config.packages = packages;
+
+        Ok(config)
+    }
+
+    pub fn package(&self, name: &str) -> PackageConfig {
+        self.packages.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn preset(&self, name: &str) -> Result<&Preset> {
+        self.presets.get(name).ok_or_else(|| {
+            let mut names = self.presets.keys().map(|x| x.as_str()).collect::<Vec<_>>();
+            names.sort();
+
+            let error = Error::new(format!("unknown preset {}", name.bold()));
+            if names.is_empty() {
+                error.with_note(format!(
+                    "presets are defined in {}",
+                    self.project_file.display()
+                ))
+            } else {
+                error.with_note(format!("available presets: {}", names.join(", ")))
+            }
+        })
+    }
+}
+
+fn parse_project(file: &Path, root: &Path) -> Result<Config> {
+    let value = std::fs::read_to_string(file)?
+        .parse::<toml::Table>()
+        .map_err(|e| Error::new(format!("failed to parse {}", PROJECT_FILE.bold())).with_note(e))?;
+
+    let mut config = Config::default();
+    let value = toml_to_json(toml::Value::Table(value));
+
+    for (key, value) in expect_table(PROJECT_FILE, &value, PROJECT_KEYS)? {
+        match key {
+            "preset" => {
+                for (name, value) in expect_entries(key, value)? {
+                    let path = format!("preset.{}", name);
+                    let preset = parse_preset(&path, value, root)?;
+                    config.presets.insert(name.to_string(), preset);
+                }
+            }
+            "tools" => {
+                for (name, value) in expect_table(key, value, TOOLS_KEYS)? {
+                    let path = format!("{}.{}", key, name);
+                    let tool = root.join(expect_string(&path, value)?);
+                    config.tools.insert(name.to_string(), tool);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(config)
+}
+
+fn parse_preset(path: &str, value: &JsonValue, root: &Path) -> Result<Preset> {
+    let mut preset = Preset::default();
+
+    for (key, value) in expect_table(path, value, PRESET_KEYS)? {
+        let path = format!("{}.{}", path, key);
+        match key {
+            "packages" => preset.packages = Some(expect_array(&path, value, expect_string)?),
+            "formats" => preset.formats = Some(parse_formats(&path, value)?),
+            "vst3-sdk" => preset.vst3_sdk = Some(parse_vst3_sdk(&path, value)?),
+            "targets" => preset.targets = Some(parse_targets(&path, value)?),
+            "profile" => preset.profile = Some(expect_string(&path, value)?),
+            "features" => preset.features = Some(expect_array(&path, value, expect_string)?),
+            "all-features" => preset.all_features = Some(expect_bool(&path, value)?),
+            "no-default-features" => preset.no_default_features = Some(expect_bool(&path, value)?),
+            "install-targets" => preset.install_targets = Some(expect_bool(&path, value)?),
+            "install" => preset.install = parse_install(&path, value)?,
+            "install-dir" => preset.install_dir = Some(root.join(expect_string(&path, value)?)),
+            "install-dir-clap" => {
+                preset.install_dir_clap = Some(root.join(expect_string(&path, value)?))
+            }
+            "install-dir-vst3" => {
+                preset.install_dir_vst3 = Some(root.join(expect_string(&path, value)?))
+            }
+            "install-dir-auv2" => {
+                preset.install_dir_auv2 = Some(root.join(expect_string(&path, value)?))
+            }
+            "install-subfolder" => preset.install_subfolder = Some(expect_string(&path, value)?),
+            "system-wide" => preset.system_wide = Some(expect_bool(&path, value)?),
+            "validate" => preset.validate = Some(expect_bool(&path, value)?),
+            "validator" => {
+                preset.validator = Some(expect_array(&path, value, |path, value| {
+                    expect_string(path, value)?
+                        .parse()
+                        .map_err(|e: Error| e.with_note(format!("found in {}", path.bold())))
+                })?)
+            }
+            "validator-strictness" => {
+                preset.validator_strictness = Some(parse_strictness(&path, value)?)
+            }
+            "output-dir" => preset.output_dir = Some(root.join(expect_string(&path, value)?)),
+            "sign" => preset.sign = Some(parse_sign(&path, value)?),
+            _ => unreachable!(),
+        }
+    }
+
+    check_vst3_sdk(path, preset.formats.as_deref(), preset.vst3_sdk.as_ref())?;
+    Ok(preset)
+}
+
+/// `install = true` or `install = "symlink"`
+fn parse_install(path: &str, value: &JsonValue) -> Result<Option<InstallMode>> {
+    match value {
+        JsonValue::Boolean(install) => Ok(install.then_some(InstallMode::Copy)),
+        JsonValue::String(mode) => mode
+            .parse()
+            .map(Some)
+            .map_err(|e: Error| e.with_note(format!("found in {}", path.bold()))),
+        _ => Err(type_error(path, "a boolean or a string", value)),
+    }
+}
+
+fn parse_sign(path: &str, value: &JsonValue) -> Result<SignConfig> {
+    let mut sign = SignConfig::default();
+    let entries = expect_table(path, value, SIGN_KEYS)?;
+
+    if let Some(missing) = SIGN_KEYS
+        .iter()
+        .find(|key| !entries.iter().any(|(x, _)| x == *key))
+    {
+        return Err(Error::new(format!(
+            "missing key {} in {}",
+            missing.bold(),
+            path.bold()
+        )));
+    }
+
+    for (key, value) in entries {
+        let path = format!("{}.{}", path, key);
+        match key {
+            "identity" => sign.identity = expect_string(&path, value)?,
+            "team" => sign.team = expect_string(&path, value)?,
+            "username" => sign.username = expect_string(&path, value)?,
+            "password-env" => sign.password_env = expect_string(&path, value)?,
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(sign)
+}
+
+fn picobundler_table(metadata: Option<&JsonValue>) -> Option<&JsonValue> {
+    metadata
+        .and_then(|x| x.get::<HashMap<String, JsonValue>>())
+        .and_then(|x| x.get("picobundler"))
+}
+
+fn parse_workspace(path: &str, value: &JsonValue, root: &Path) -> Result<WorkspaceConfig> {
+    let mut config = WorkspaceConfig::default();
+
+    for (key, value) in expect_table(path, value, WORKSPACE_KEYS)? {
+        let path = format!("{}.{}", path, key);
+        match key {
+            "local-cmake-path" => {
+                config.local_cmake_path = Some(root.join(expect_string(&path, value)?))
+            }
+            "cmake-build-type" => {
+                for (profile, value) in expect_entries(&path, value)? {
+                    let path = format!("{}.{}", path, profile);
+                    config.cmake_build_type.insert(
+                        profile.to_string(),
+                        expect_string(&path, value)?
+                            .parse()
+                            .map_err(|e: Error| e.with_note(format!("found in {}", path.bold())))?,
+                    );
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(config)
+}
+
+fn parse_package(path: &str, value: &JsonValue) -> Result<PackageConfig> {
+    let mut config = PackageConfig::default();
+
+    for (key, value) in expect_table(path, value, PACKAGE_KEYS)? {
+        let path = format!("{}.{}", path, key);
+        match key {
+            "formats" => config.formats = Some(parse_formats(&path, value)?),
+            "vst3-sdk" => config.vst3_sdk = Some(parse_vst3_sdk(&path, value)?),
+            "name" => config.name = Some(expect_string(&path, value)?),
+            "bundle-id" => config.bundle_id = Some(expect_string(&path, value)?),
+            "vendor" => config.vendor = Some(expect_string(&path, value)?),
+            "targets" => config.targets = Some(parse_targets(&path, value)?),
+            "features" => config.features = Some(expect_array(&path, value, expect_string)?),
+            "min-glibc" => config.min_glibc = Some(parse_glibc_version(&path, value)?),
+            "allowed-libraries" => {
+                config.allowed_libraries = expect_array(&path, value, expect_string)?
+            }
+            "unknown-libraries" => {
+                config.unknown_libraries = Some(
+                    expect_string(&path, value)?
+                        .parse()
+                        .map_err(|e: Error| e.with_note(format!("found in {}", path.bold())))?,
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    check_vst3_sdk(path, config.formats.as_deref(), config.vst3_sdk.as_ref())?;
+    Ok(config)
+}
+
+fn parse_formats(path: &str, value: &JsonValue) -> Result<Vec<PluginFormat>> {
+    expect_array(path, value, |path, value| {
+        expect_string(path, value)?
+            .parse()
+            .map_err(|e: Error| e.with_note(format!("found in {}", path.bold())))
+    })
+}
+
+fn parse_targets(path: &str, value: &JsonValue) -> Result<Vec<BuildTarget>> {
+    expect_array(path, value, |path, value| {
+        expect_string(path, value)?.parse()
+    })
+}
+
+fn parse_glibc_version(path: &str, value: &JsonValue) -> Result<String> {
+    let version = expect_string(path, value)?;
+    let valid = version
+        .split('.')
+        .all(|x| !x.is_empty() && x.chars().all(|x| x.is_ascii_digit()));
+
+    match valid {
+        true => Ok(version),
+        false => Err(Error::new(format!(
+            "invalid glibc version {} in {}",
+            version.bold(),
+            path.bold()
+        ))
+        .with_note(format!("use a version like {}", "\"2.28\"".bold()))),
+    }
+}
+
+fn parse_vst3_sdk(path: &str, value: &JsonValue) -> Result<Vst3Sdk> {
+    expect_string(path, value)?
+        .parse()
+        .map_err(|e: Error| e.with_note(format!("found in {}", path.bold())))
+}
+
+fn check_vst3_sdk(
+    path: &str,
+    formats: Option<&[PluginFormat]>,
+    vst3_sdk: Option<&Vst3Sdk>,
+) -> Result<()> {
+    let wants_vst3 = formats.is_some_and(|x| x.contains(&PluginFormat::Vst3));
+    if wants_vst3 && vst3_sdk.is_none() {
+        return Err(Error::new(format!(
+            "{} is required when building {} plugins",
+            format!("{}.vst3-sdk", path).bold(),
+            PluginFormat::Vst3.print_name()
+        ))
+        .with_note(format!(
+            "set it to either {} or {}",
+            "gpl".bold().bright_cyan(),
+            "proprietary".bold().bright_green()
+        )));
+    }
+
+    Ok(())
+}
+
+fn parse_strictness(path: &str, value: &JsonValue) -> Result<u32> {
+    let level = value
+        .get::<f64>()
+        .ok_or_else(|| type_error(path, "a number", value))?;
+
+    match level.fract() == 0.0 && (1.0..=10.0).contains(level) {
+        true => Ok(*level as u32),
+        false => Err(Error::new(format!(
+            "{} must be a whole number from 1 to 10",
+            path.bold()
+        ))),
+    }
+}
+
+fn expect_entries<'a>(path: &str, value: &'a JsonValue) -> Result<Vec<(&'a str, &'a JsonValue)>> {
+    let table = value
+        .get::<HashMap<String, JsonValue>>()
+        .ok_or_else(|| type_error(path, "a table", value))?;
+
+    let mut entries = table
+        .iter()
+        .map(|(key, value)| (key.as_str(), value))
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|(key, _)| *key);
+
+    Ok(entries)
+}
+
+fn expect_table<'a>(
+    path: &str,
+    value: &'a JsonValue,
+    keys: &[&str],
+) -> Result<Vec<(&'a str, &'a JsonValue)>> {
+    let entries = expect_entries(path, value)?;
+    if let Some((key, _)) = entries.iter().find(|(key, _)| !keys.contains(key)) {
+        return Err(
+            Error::new(format!("unknown key {} in {}", key.bold(), path.bold()))
+                .with_note(format!("expected one of: {}", keys.join(", "))),
+        );
+    }
+
+    Ok(entries)
+}
+
+fn expect_string(path: &str, value: &JsonValue) -> Result<String> {
+    value
+        .get::<String>()
+        .cloned()
+        .ok_or_else(|| type_error(path, "a string", value))
+}
+
+fn expect_bool(path: &str, value: &JsonValue) -> Result<bool> {
+    value
+        .get::<bool>()
+        .copied()
+        .ok_or_else(|| type_error(path, "a boolean", value))
+}
+
+fn expect_array<T>(
+    path: &str,
+    value: &JsonValue,
+    item: impl Fn(&str, &JsonValue) -> Result<T>,
+) -> Result<Vec<T>> {
+    value
+        .get::<Vec<JsonValue>>()
+        .ok_or_else(|| type_error(path, "an array", value))?
+        .iter()
+        .enumerate()
+        .map(|(index, value)| item(&format!("{}[{}]", path, index), value))
+        .collect()
+}
+
+fn toml_to_json(value: toml::Value) -> JsonValue {
+    match value {
+        toml::Value::String(x) => JsonValue::String(x),
+        toml::Value::Integer(x) => JsonValue::Number(x as f64),
+        toml::Value::Float(x) => JsonValue::Number(x),
+        toml::Value::Boolean(x) => JsonValue::Boolean(x),
+        toml::Value::Datetime(x) => JsonValue::String(x.to_string()),
+        toml::Value::Array(x) => JsonValue::Array(x.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(x) => {
+            JsonValue::Object(x.into_iter().map(|(k, v)| (k, toml_to_json(v))).collect())
+        }
+    }
+}
+
+fn type_error(path: &str, expected: &str, value: &JsonValue) -> Error {
+    let found = match value {
+        JsonValue::Number(_) => "a number",
+        JsonValue::Boolean(_) => "a boolean",
+        JsonValue::String(_) => "a string",
+        JsonValue::Null => "null",
+        JsonValue::Array(_) => "an array",
+        JsonValue::Object(_) => "a table",
+    };
+
+    Error::new(format!(
+        "expected {} for {}, found {}",
+        expected,
+        path.bold(),
+        found
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn parse(json: &str) -> Result<PackageConfig> {
+        parse_package("picobundler", &JsonValue::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn test_parse_package() {
+        let config = parse(
+            r#"{"formats": ["clap", "vst3"], "vst3-sdk": "GPL", "name": "Gain", "features": ["a"], "min-glibc": "2.28", "allowed-libraries": ["libasound.so.2"], "unknown-libraries": "deny"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.formats,
+            Some(vec![PluginFormat::Clap, PluginFormat::Vst3])
+        );
+        assert_eq!(config.name.as_deref(), Some("Gain"));
+        assert_eq!(config.features, Some(vec!["a".to_string()]));
+        assert_eq!(config.min_glibc.as_deref(), Some("2.28"));
+        assert_eq!(config.allowed_libraries, vec!["libasound.so.2".to_string()]);
+        assert_eq!(config.unknown_libraries, Some(UnknownLibraries::Deny));
+        assert!(matches!(config.vst3_sdk, Some(Vst3Sdk::OpenSource)));
+
+        assert!(parse(r#"{"format": ["clap"]}"#).is_err());
+        assert!(parse(r#"{"formats": "clap"}"#).is_err());
+        assert!(parse(r#"{"formats": ["vst3"]}"#).is_err());
+        assert!(parse(r#"{"min-glibc": "2.x"}"#).is_err());
+        assert!(parse(r#"{"unknown-libraries": "error"}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_preset() {
+        let parse = |json: &str| {
+            parse_preset(
+                "preset.ci",
+                &JsonValue::from_str(json).unwrap(),
+                Path::new("."),
+            )
+        };
+
+        let preset = parse(r#"{"validator": ["pluginval"], "validator-strictness": 5}"#).unwrap();
+        assert_eq!(preset.validator, Some(vec![Validator::Pluginval]));
+        assert_eq!(preset.validator_strictness, Some(5));
+        assert_eq!(parse("{}").unwrap().validator_strictness, None);
+
+        assert!(parse(r#"{"validator-strictness": 11}"#).is_err());
+        assert!(parse(r#"{"validator-strictness": 2.5}"#).is_err());
+        assert!(parse(r#"{"validator-strictness": "5"}"#).is_err());
+    }
+}
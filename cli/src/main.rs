@@ -2,133 +2,741 @@
 
 use args::ArgsVst3;
 use build::{
-    cargo_workspace_dir, codesign_bundle, notarize_bundle, plugin_system_folder, reflink,
-    reload_audio_unit_cache, run_parallel, wait_unlink,
+    BuildPackage, BuildRequest, DoctorRequest, FileWatcher, InstallMode, PluginFormat, Validator,
+    Vst3Sdk, bundle_size, cargo_source_dirs, cargo_workspace_dir, codesign_bundle, find_validator,
+    inspect_clap, install_bundle, notarize_bundle, reflink, reload_audio_unit_cache, run_doctor,
+    run_parallel, run_validator, uninstall_bundles, validate_clap_isolated, wait_unlink, zig_cc,
 };
-use cli::{Error, print_error, report_message, report_span};
+use cli::{
+    Error, Result, StatusReporter, print_error, remove_on_interrupt, report_message, report_span,
+    set_interrupt_handler,
+};
+use config::Config;
 use owo_colors::OwoColorize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tinyjson::JsonValue;
 
 mod args;
 mod build;
 mod cli;
+mod config;
 
 fn main() {
-    print_error(|| {
-        let args::Args {
-            clap,
-            auv2,
-            vst3,
-            mut build,
-            codesign,
-            verbose,
-            install,
-        } = args::parse_args();
+    set_interrupt_handler();
+    print_error(|| match args::parse_args() {
+        args::ArgsCommand::Bundle(args) => bundle(args),
+        args::ArgsCommand::Watch(args) => watch(args),
+        args::ArgsCommand::Inspect(args) => inspect(args),
+        args::ArgsCommand::Uninstall(args) => uninstall(args),
+        args::ArgsCommand::Doctor(args) => doctor(args),
+        args::ArgsCommand::ValidateClap(args) => validate_clap(args),
+        args::ArgsCommand::ZigCc(args) => zig_cc(&args.zig_triple, args.args),
+    });
+}
 
-        if verbose {
-            cli::set_force_log(true);
-        }
+fn bundle(args: args::Args) -> Result<()> {
+    let (args, config, workspace_dir) = load_args(args)?;
+    let result = bundle_packages(&args, &config, &workspace_dir);
+    let timings = write_timings(&args, &workspace_dir);
+
+    result?;
+    timings
+}
+
+fn watch(args: args::Args) -> Result<()> {
+    let (mut args, config, workspace_dir) = load_args(args)?;
+    args.build.target = vec![target_lexicon::HOST.to_string()];
+
+    let mut dirs = Vec::new();
+    for package in &args.build.packages {
+        dirs.extend(cargo_source_dirs(package)?);
+    }
 
-        let clap = clap || vst3 == ArgsVst3::None && !auv2;
-        if build.packages.is_empty() {
-            return Err(Error::new("no packages specified"));
+    let watcher = FileWatcher::new(
+        &dirs,
+        vec![
+            workspace_dir.join("target"),
+            output_dir(&args, &workspace_dir),
+        ],
+    )?;
+
+    loop {
+        // compile errors are shown, but only stop this round
+        if let Err(e) = bundle_packages(&args, &config, &workspace_dir) {
+            e.print();
         }
 
-        if build.target.is_empty() {
-            build.target.push(target_lexicon::HOST.to_string());
+        if let Err(e) = write_timings(&args, &workspace_dir) {
+            e.print();
         }
 
-        let workspace_dir = cargo_workspace_dir()?;
-        let output_dir = workspace_dir.join("target").join("bundled");
+        eprintln!("{}", "watching for changes...".bold().bright_blue());
 
-        let build_request = build::BuildRequest {
-            target_dir: workspace_dir.join("target"),
-            packages: build.packages,
-            profile: build.profile.unwrap_or("release".to_string()),
+        // changes made during the build are picked up here, so at most one build is queued
+        let changes = watcher.wait(Duration::from_millis(300))?;
+        eprintln!("{} changed, rebuilding", changes[0].display().bold());
+    }
+}
 
-            targets: build
-                .target
-                .into_iter()
-                .map(|x| x.parse())
-                .collect::<Result<_, Error>>()?,
-
-            features: build.features,
-            all_features: build.all_features,
-            no_default_features: build.no_default_features,
-
-            clap,
-            auv2,
-            vst3: match vst3 {
-                ArgsVst3::Gpl => Some(build::Vst3Sdk::OpenSource),
-                ArgsVst3::Proprietary => Some(build::Vst3Sdk::Proprietary),
-                ArgsVst3::None => None,
-            },
-        };
+fn load_args(mut args: args::Args) -> Result<(args::Args, Config, PathBuf)> {
+    if args.verbose {
+        cli::set_force_log(true);
+    }
+
+    cli::set_message_format(args.message_format);
+
+    if args.timings {
+        StatusReporter::get().record_timings();
+    }
+
+    let workspace_dir = cargo_workspace_dir()?;
+    let config = Config::load(&workspace_dir)?;
+
+    if let Some(preset) = args.preset.clone() {
+        args = args.with_preset(config.preset(&preset)?)?;
+    }
+
+    if args.build.packages.is_empty() {
+        return Err(Error::new("no packages specified"));
+    }
+
+    cli::open_log(&output_dir(&args, &workspace_dir).join("logs"))?;
+    cli::write_log(&format!(
+        "picobundler {}, packages: {}",
+        env!("CARGO_PKG_VERSION"),
+        args.build.packages.join(", ")
+    ));
+
+    Ok((args, config, workspace_dir))
+}
+
+fn output_dir(args: &args::Args, workspace_dir: &Path) -> PathBuf {
+    args.output_dir
+        .clone()
+        .unwrap_or_else(|| workspace_dir.join("target").join("bundled"))
+}
+
+fn write_timings(args: &args::Args, workspace_dir: &Path) -> Result<()> {
+    if args.timings {
+        let summary = cli::write_timings(&output_dir(args, workspace_dir).join("timings"))?;
+        eprintln!("{} {}", "timings written to".bold(), summary.display());
+    }
+
+    Ok(())
+}
+
+fn bundle_packages(args: &args::Args, config: &Config, workspace_dir: &Path) -> Result<()> {
+    let output_dir = output_dir(args, workspace_dir);
+
+    let validators = args
+        .validator
+        .iter()
+        .map(|x| {
+            let validator = x.parse::<Validator>()?;
+            let program = find_validator(validator, config.tools.get(x).map(|x| x.as_path()))?;
+            Ok((validator, program))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let request = build_request(args, config, workspace_dir)?;
+    let artifacts = build::build(&request)?;
+
+    if args.validate.unwrap_or_default() {
+        validate(&artifacts)?;
+    }
+
+    let install_location = args.install_location.location();
+    let codesign = args.codesign.as_ref();
+    let install = args.install;
+
+    let bundles = run_parallel(artifacts, |artifact| {
+        report_span!(
+            "copying {} {} ({}) to the output directory",
+            artifact.format.print_name().bold(),
+            artifact.package.bold(),
+            artifact.target.to_string().bold()
+        );
+
+        let output_path = output_dir
+            .join(artifact.target.to_string())
+            .join(&artifact.name)
+            .with_extension(artifact.format.extension());
 
-        let artifacts = build::build(&build_request)?;
+        // the bundle is only complete once it's signed
+        let cleanup = remove_on_interrupt(&output_path);
+        let _ = std::fs::create_dir_all(&output_path);
+        wait_unlink(&output_path)?;
+        reflink(&artifact.path, &output_path)?;
 
-        run_parallel(artifacts, |artifact| {
-            report_span!(
-                "copying {} {} ({}) to the output directory",
+        let is_apple = matches!(
+            artifact.target.operating_system(),
+            target_lexicon::OperatingSystem::MacOSX(_) | target_lexicon::OperatingSystem::Darwin(_)
+        );
+
+        if is_apple {
+            if let Some(codesign) = codesign {
+                codesign_bundle(&output_path, Some(&codesign.identity))?;
+                notarize_bundle(
+                    &output_path,
+                    &codesign.team,
+                    &codesign.username,
+                    &codesign.password,
+                )?;
+            } else {
+                codesign_bundle(&output_path, None)?;
+            }
+        }
+        drop(cleanup);
+
+        // an explicit directory doesn't depend on the host, so bundles for other targets can be
+        // installed there too, e.g. onto a mounted drive of another machine
+        let can_install = artifact.target.is_supported(&target_lexicon::HOST)
+            || install_location.explicit_dir(artifact.format).is_some();
+
+        let mut install_path = None;
+        if let (Some(mode), true) = (install, can_install) {
+            report_message!(
+                "installing {} {} ({})",
                 artifact.format.print_name().bold(),
                 artifact.package.bold(),
                 artifact.target.to_string().bold()
             );
 
-            let output_path = output_dir
-                .join(artifact.target.to_string())
-                .join(&artifact.package)
-                .with_extension(artifact.format.extension());
+            let bundle = match mode {
+                InstallMode::Copy => &artifact.path,
+                InstallMode::Symlink => &output_path,
+            };
 
-            let _ = std::fs::create_dir_all(&output_path);
-            wait_unlink(&output_path)?;
-            reflink(&artifact.path, &output_path)?;
+            install_path = Some(install_bundle(
+                bundle,
+                &install_location.folder(artifact.format, target_lexicon::HOST.operating_system)?,
+                &format!("{}.{}", artifact.name, artifact.format.extension()),
+                &artifact.package,
+                mode,
+            )?);
+        }
 
-            let is_apple = matches!(
-                artifact.target.operating_system(),
-                target_lexicon::OperatingSystem::MacOSX(_)
-                    | target_lexicon::OperatingSystem::Darwin(_)
-            );
+        report_artifact(&artifact, &output_path, install_path.as_deref());
+        Ok((artifact, output_path))
+    })?;
 
-            if is_apple {
-                if let Some(codesign) = codesign.as_ref() {
-                    codesign_bundle(&output_path, Some(&codesign.identity))?;
-                    notarize_bundle(
-                        &output_path,
-                        &codesign.team,
-                        &codesign.username,
-                        &codesign.password,
-                    )?;
-                } else {
-                    codesign_bundle(&output_path, None)?;
-                }
+    print_audit(&bundles);
+
+    if install.is_some() {
+        reload_audio_unit_cache()?;
+    }
+
+    if !validators.is_empty() {
+        run_validators(
+            &validators,
+            args.validator_strictness,
+            &bundles,
+            &output_dir,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Prints what the audit of the built binaries found above the status bar, so it stays visible.
+fn print_audit(bundles: &[(build::BuildArtifact, PathBuf)]) {
+    for (artifact, bundle) in bundles {
+        if let Some(glibc) = &artifact.glibc {
+            let versions = [("glibc", &glibc.glibc), ("libstdc++", &glibc.glibcxx)]
+                .into_iter()
+                .filter_map(|(name, version)| Some(format!("{} {}", name, version.as_ref()?)))
+                .collect::<Vec<_>>();
+
+            if !versions.is_empty() {
+                cli::print_line(&format!(
+                    "{} needs {}",
+                    bundle.display().bold(),
+                    versions.join(", ")
+                ));
+            }
+        }
+
+        if artifact.libraries.is_empty() {
+            continue;
+        }
+
+        let unexpected = artifact.libraries.iter().filter(|x| !x.allowed).count();
+        let libraries = artifact
+            .libraries
+            .iter()
+            .map(|x| match x.allowed {
+                true => x.name.clone(),
+                false => x.name.bold().bright_red().to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        cli::print_line(&format!(
+            "{} links {}{}",
+            bundle.display().bold(),
+            libraries.join(", "),
+            match unexpected {
+                0 => String::new(),
+                n => format!(" ({} not on the allowlist)", n)
+                    .bright_red()
+                    .to_string(),
             }
+        ));
+    }
+}
 
-            if install && artifact.target.is_supported(&target_lexicon::HOST) {
-                report_message!(
-                    "installing {} {} ({})",
-                    artifact.format.print_name().bold(),
+/// Emits the `artifact` JSON event for `--message-format json`.
+fn report_artifact(artifact: &build::BuildArtifact, bundle: &Path, install_path: Option<&Path>) {
+    // walks the whole bundle, so it's skipped unless someone reads the event
+    if !cli::json_messages() {
+        return;
+    }
+
+    let path = |x: &Path| cli::json_string(x.to_string_lossy());
+    let version = |x: Option<&str>| x.map_or(JsonValue::Null, cli::json_string);
+    let glibc = artifact.glibc.as_ref();
+    cli::emit_json(
+        "artifact",
+        [
+            ("package", cli::json_string(&artifact.package)),
+            ("name", cli::json_string(&artifact.name)),
+            ("target", cli::json_string(artifact.target.to_string())),
+            ("format", cli::json_string(artifact.format.print_name())),
+            ("bundle", path(bundle)),
+            ("install", install_path.map_or(JsonValue::Null, path)),
+            (
+                "size",
+                bundle_size(bundle).map_or(JsonValue::Null, |x| JsonValue::Number(x as f64)),
+            ),
+            (
+                "duration",
+                JsonValue::Number(artifact.duration.as_secs_f64()),
+            ),
+            ("glibc", version(glibc.and_then(|x| x.glibc.as_deref()))),
+            ("glibcxx", version(glibc.and_then(|x| x.glibcxx.as_deref()))),
+            (
+                "libraries",
+                JsonValue::Array(
+                    artifact
+                        .libraries
+                        .iter()
+                        .map(|x| {
+                            JsonValue::Object(HashMap::from([
+                                ("name".to_string(), cli::json_string(&x.name)),
+                                ("allowed".to_string(), JsonValue::Boolean(x.allowed)),
+                            ]))
+                        })
+                        .collect(),
+                ),
+            ),
+        ],
+    );
+}
+
+fn run_validators(
+    validators: &[(Validator, PathBuf)],
+    strictness: Option<u32>,
+    bundles: &[(build::BuildArtifact, PathBuf)],
+    output_dir: &Path,
+) -> Result<()> {
+    let mut failures = Vec::new();
+    let mut count = 0;
+
+    for (validator, program) in validators {
+        for (artifact, bundle) in bundles {
+            if !validator.supports(artifact.format)
+                || !artifact.target.is_supported(&target_lexicon::HOST)
+            {
+                continue;
+            }
+
+            let report = output_dir
+                .join(artifact.target.to_string())
+                .join("validation")
+                .join(format!(
+                    "{}.{}.{}.log",
+                    artifact.name,
+                    artifact.format.extension(),
+                    validator
+                ));
+
+            count += 1;
+            if let Err(e) = run_validator(*validator, program, strictness, bundle, &report) {
+                failures.push(format!(
+                    "{} failed for {} {} ({}), see {}",
+                    validator.bold(),
+                    artifact.format.print_name(),
                     artifact.package.bold(),
-                    artifact.target.to_string().bold()
+                    artifact.target,
+                    report.display()
+                ));
+
+                report_message!("{}", e.message());
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    Err(failures.iter().fold(
+        Error::new(format!(
+            "{} of {} validator runs failed",
+            failures.len(),
+            count
+        )),
+        |error, failure| error.with_note(failure),
+    ))
+}
+
+fn validate(artifacts: &[build::BuildArtifact]) -> Result<()> {
+    let mut failures = Vec::new();
+    for artifact in artifacts {
+        if artifact.format != PluginFormat::Clap
+            || !artifact.target.is_supported(&target_lexicon::HOST)
+        {
+            continue;
+        }
+
+        if let Err(e) = validate_clap_isolated(&artifact.path) {
+            failures.push((artifact, e));
+        }
+    }
+
+    match failures.len() {
+        0 => Ok(()),
+        1 => Err(failures.pop().unwrap().1),
+        count => Err(failures.into_iter().fold(
+            Error::new(format!("{} plugins failed validation", count)),
+            |error, (artifact, e)| {
+                e.notes().iter().fold(
+                    error.with_note(format!(
+                        "{} ({}): {}",
+                        artifact.package.bold(),
+                        artifact.target,
+                        e.message()
+                    )),
+                    |error, note| error.with_note(format!("  {}", note)),
+                )
+            },
+        )),
+    }
+}
+
+/// The child process side of `--validate`. Failures are printed without formatting, one per
+/// line and prefixed, so the parent can tell them apart from the plugin's own output.
+fn validate_clap(args: args::ArgsInspect) -> Result<()> {
+    if let Err(e) = build::validate_clap(&args.bundle) {
+        eprintln!("{}{}", build::CLAP_VALIDATION_FAILURE, e.message());
+        for note in e.notes() {
+            eprintln!("{}{}", build::CLAP_VALIDATION_FAILURE, note);
+        }
+
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn uninstall(args: args::ArgsUninstall) -> Result<()> {
+    if args.packages.is_empty() && !args.all {
+        return Err(Error::new("no packages specified")
+            .with_note(format!("use {} to uninstall every plugin", "--all".bold())));
+    }
+
+    let config = Config::load(&cargo_workspace_dir()?)?;
+    let packages = (!args.all).then_some(args.packages.as_slice());
+    let install_location = args.install_location.location();
+
+    let mut folders = Vec::new();
+    let mut removed = Vec::new();
+    for format in [PluginFormat::Clap, PluginFormat::Vst3, PluginFormat::Auv2] {
+        let Ok(folder) = install_location.folder(format, target_lexicon::HOST.operating_system)
+        else {
+            continue;
+        };
+
+        // with --install-dir all formats share the same folder
+        if !folders.contains(&folder) {
+            removed.extend(uninstall_bundles(&folder, packages)?);
+            folders.push(folder.clone());
+        }
+
+        for package in packages.into_iter().flatten() {
+            let name = config.package(package).name.unwrap_or(package.clone());
+            let path = folder.join(format!("{}.{}", name, format.extension()));
+            if path.exists() {
+                report_message!(
+                    "skipping {}, it was not installed by picobundler",
+                    path.display()
                 );
+            }
+        }
+    }
+
+    let audio_unit_extension = PluginFormat::Auv2.extension();
+    if removed
+        .iter()
+        .any(|x| x.extension().is_some_and(|x| x == audio_unit_extension))
+    {
+        reload_audio_unit_cache()?;
+    }
 
-                let install_path =
-                    plugin_system_folder(artifact.format, target_lexicon::HOST.operating_system)?
-                        .join("dev")
-                        .join(&artifact.package)
-                        .with_extension(artifact.format.extension());
+    if removed.is_empty() {
+        println!("nothing to uninstall");
+    }
 
-                let _ = std::fs::create_dir_all(&install_path);
-                wait_unlink(&install_path)?;
-                reflink(&artifact.path, &install_path)?;
+    for path in removed {
+        println!("{} {}", "removed".bold().bright_green(), path.display());
+    }
+
+    Ok(())
+}
+
+fn doctor(args: args::ArgsDoctor) -> Result<()> {
+    let targets = match args.target.is_empty() {
+        true => vec![target_lexicon::HOST.to_string().parse()?],
+        false => args
+            .target
+            .iter()
+            .map(|x| x.parse())
+            .collect::<Result<_>>()?,
+    };
+
+    let checks = run_doctor(&DoctorRequest {
+        targets,
+        auv2: args.auv2,
+        vst3: match args.vst3 {
+            ArgsVst3::Gpl => Some(Vst3Sdk::OpenSource),
+            ArgsVst3::Proprietary => Some(Vst3Sdk::Proprietary),
+            ArgsVst3::None => None,
+        },
+        sign: args.sign,
+    });
+
+    let width = checks
+        .iter()
+        .map(|x| x.name.len())
+        .max()
+        .unwrap_or_default();
+    for check in &checks {
+        match &check.result {
+            Ok(detail) => println!(
+                "{} {:<width$}  {} {}",
+                "ok  ".bold().bright_green(),
+                check.name.bold(),
+                detail,
+                format!("({})", check.needed_for).bright_black(),
+            ),
+            Err(e) => {
+                println!(
+                    "{} {:<width$}  {} {}",
+                    "fail".bold().bright_red(),
+                    check.name.bold(),
+                    e.message(),
+                    format!("({})", check.needed_for).bright_black(),
+                );
+                for note in e.notes() {
+                    println!(
+                        "     {:<width$}  {} {}",
+                        "",
+                        "hint:".bold().bright_cyan(),
+                        note
+                    );
+                }
             }
+        }
+    }
+
+    let failed = checks.iter().filter(|x| x.result.is_err()).count();
+    match failed {
+        0 => Ok(()),
+        _ => Err(Error::new(format!(
+            "{} of {} checks failed",
+            failed,
+            checks.len()
+        ))),
+    }
+}
 
-            Ok(())
-        })?;
+fn inspect(args: args::ArgsInspect) -> Result<()> {
+    let plugins = inspect_clap(&args.bundle)?;
+    if plugins.is_empty() {
+        return Err(Error::new(format!(
+            "{} does not expose any plugins",
+            args.bundle.display().bold()
+        )));
+    }
 
-        if install {
-            reload_audio_unit_cache()?;
+    for plugin in plugins {
+        println!(
+            "{} {} {}",
+            plugin.name.bold().bright_blue(),
+            plugin.version,
+            format!("({})", plugin.id).bright_black()
+        );
+        println!("  {}: {}", "vendor".bold(), plugin.vendor);
+        if !plugin.description.is_empty() {
+            println!("  {}: {}", "description".bold(), plugin.description);
         }
+        println!("  {}: {}", "features".bold(), plugin.features.join(", "));
 
-        Ok(())
-    });
+        let audio_ports = [
+            ("audio inputs", &plugin.audio_inputs),
+            ("audio outputs", &plugin.audio_outputs),
+        ];
+
+        for (label, ports) in audio_ports {
+            println!("  {}:", label.bold());
+            if ports.is_empty() {
+                println!("    {}", "none".bright_black());
+            }
+            for port in ports {
+                println!(
+                    "    #{} {} - {} channel(s){}{}",
+                    port.id,
+                    port.name,
+                    port.channel_count,
+                    port.port_type
+                        .as_ref()
+                        .map(|x| format!(", {}", x))
+                        .unwrap_or_default(),
+                    if port.is_main { ", main" } else { "" }
+                );
+            }
+        }
+
+        let note_ports = [
+            ("note inputs", &plugin.note_inputs),
+            ("note outputs", &plugin.note_outputs),
+        ];
+
+        for (label, ports) in note_ports {
+            println!("  {}:", label.bold());
+            if ports.is_empty() {
+                println!("    {}", "none".bright_black());
+            }
+            for port in ports {
+                println!(
+                    "    #{} {} - {}",
+                    port.id,
+                    port.name,
+                    port.dialects.join(", ")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Merges the command line arguments with the `[package.metadata.picobundler]` table of every
+/// package, the command line always takes precedence.
+fn build_request(args: &args::Args, config: &Config, workspace_dir: &Path) -> Result<BuildRequest> {
+    let profile = args.build.profile.clone().unwrap_or("release".to_string());
+
+    Ok(BuildRequest {
+        target_dir: workspace_dir.join("target"),
+        cmake_dir: config.workspace.local_cmake_path.clone(),
+
+        packages: args
+            .build
+            .packages
+            .iter()
+            .map(|package| build_package(args, config, package))
+            .collect::<Result<_>>()?,
+        build_type: config.workspace.cmake_build_type.get(&profile).copied(),
+        profile,
+
+        all_features: args.build.all_features.unwrap_or_default(),
+        no_default_features: args.build.no_default_features.unwrap_or_default(),
+        install_targets: args.build.install_targets.unwrap_or_default(),
+
+        jobs: args
+            .build
+            .jobs
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |x| x.get())),
+    })
+}
+
+fn build_package(args: &args::Args, config: &Config, package: &str) -> Result<BuildPackage> {
+    let package_config = config.package(package);
+    let preset = args
+        .preset
+        .as_deref()
+        .map(|x| config.preset(x))
+        .transpose()?;
+
+    // a preset is shared by many packages, so it must not add formats to one that has its own
+    let formats = match (&package_config.formats, preset) {
+        (Some(formats), _) => Some((formats, package_config.vst3_sdk.clone())),
+        (None, Some(preset)) => preset
+            .formats
+            .as_ref()
+            .map(|x| (x, preset.vst3_sdk.clone())),
+        (None, None) => None,
+    };
+
+    let (clap, auv2, vst3) = if args.clap || args.auv2 || args.vst3 != ArgsVst3::None {
+        (
+            args.clap || args.vst3 == ArgsVst3::None && !args.auv2,
+            args.auv2,
+            match args.vst3 {
+                ArgsVst3::Gpl => Some(Vst3Sdk::OpenSource),
+                ArgsVst3::Proprietary => Some(Vst3Sdk::Proprietary),
+                ArgsVst3::None => None,
+            },
+        )
+    } else if let Some((formats, vst3_sdk)) = formats {
+        (
+            formats.contains(&PluginFormat::Clap),
+            formats.contains(&PluginFormat::Auv2),
+            formats
+                .contains(&PluginFormat::Vst3)
+                .then_some(vst3_sdk)
+                .flatten(),
+        )
+    } else {
+        (true, false, None)
+    };
+
+    let targets = if !args.build.target.is_empty() {
+        args.build
+            .target
+            .iter()
+            .map(|x| x.parse())
+            .collect::<Result<_>>()?
+    } else if let Some(targets) = package_config.targets.clone() {
+        targets
+    } else {
+        vec![target_lexicon::HOST.to_string().parse()?]
+    };
+
+    let features = if !args.build.features.is_empty() {
+        args.build.features.clone()
+    } else {
+        package_config.features.clone().unwrap_or_default()
+    };
+
+    Ok(BuildPackage {
+        name: package.to_string(),
+        display_name: package_config.name,
+        bundle_id: package_config.bundle_id,
+        vendor: package_config.vendor,
+
+        targets,
+        features,
+
+        clap,
+        auv2,
+        vst3,
+
+        min_glibc: package_config.min_glibc,
+        allowed_libraries: package_config.allowed_libraries,
+        unknown_libraries: package_config.unknown_libraries.unwrap_or_default(),
+    })
 }